@@ -1,15 +1,11 @@
-use tokio::sync::mpsc;
-use tokio::sync::mpsc::UnboundedReceiver;
-use tokio::sync::mpsc::UnboundedSender;
-
+use crate::client_connection::Connection;
 use crate::*;
-use crate::{client_connection::Connection, encde::*, frame::*};
-
-use self::properties::PropertiesBuilder;
 
 pub struct Client {
     connection: client_connection::Connection,
     channel_id: u16,
+    // Whether the per-consumer prefetch on the channel came from ConsumeOptions
+    options_prefetch: bool,
 }
 
 impl Client {
@@ -20,6 +16,7 @@ impl Client {
         Self {
             connection,
             channel_id,
+            options_prefetch: false,
         }
     }
 
@@ -28,11 +25,19 @@ impl Client {
         Ok(())
     }
 
-    pub async fn create_queue(&mut self, queue_definition: QueueDefinition) -> Result<String> {
+    pub async fn create_queue(&mut self, queue_definition: QueueDefinition) -> Result<QueueInfo> {
         let queue = self.connection.create_queue(queue_definition).await?;
         Ok(queue)
     }
 
+    // Passively declares the queue, failing with Error::QueueNotFound if it does not
+    // exist. This uses a separate channel, consumers and settings on the client's
+    // channel are not affected by the failure.
+    pub async fn queue_info(&mut self, queue_name: &str) -> Result<QueueInfo> {
+        let queue = self.connection.queue_info(queue_name).await?;
+        Ok(queue)
+    }
+
     pub async fn create_exchange(
         &mut self,
         exchange: String,
@@ -49,144 +54,266 @@ impl Client {
         Ok(())
     }
 
-    pub async fn send_message(
+    /// Limits the number of unacknowledged messages the server will deliver.
+    ///
+    /// RabbitMQ reinterprets the `global` flag of `basic.qos`: with `global` false the
+    /// limit applies separately to each consumer started on this channel afterwards,
+    /// with `global` true it is shared between all consumers on the channel. A `count`
+    /// of 0 means unlimited. `size` (prefetch in octets) is not supported by RabbitMQ
+    /// and should be left as 0.
+    pub async fn set_prefetch(&mut self, count: u16, size: u32, global: bool) -> Result<()> {
+        self.connection.set_prefetch(count, size, global).await?;
+        if !global {
+            self.options_prefetch = false;
+        }
+        Ok(())
+    }
+
+    // Stream of messages published with `mandatory` that the broker could not route.
+    // Only the most recently returned stream receives them.
+    pub fn returned_messages(&self) -> tokio::sync::mpsc::UnboundedReceiver<ReturnedMessage> {
+        self.connection.returned_messages()
+    }
+
+    // Stream of connection.blocked/unblocked and channel.flow notifications, and of
+    // errors in the background tasks
+    pub fn connection_events(&self) -> tokio::sync::mpsc::UnboundedReceiver<ConnectionEvent> {
+        self.connection.connection_events()
+    }
+
+    pub async fn publish(
         &mut self,
-        message: &str,
-        queue: &str,
         exchange: &str,
-        mandatory: bool,
-        immediate: bool,
+        routing_key: &str,
+        body: &[u8],
         properties: Properties,
-        wait_for_response: bool,
-        handler: Handler,
     ) -> Result<()> {
-        let mut full_buffer: Vec<u8> = Vec::new();
-        let publish = basic::Publish::new(self.channel_id, exchange, queue, mandatory, immediate);
-        let bytes = encode_frame(&publish).unwrap();
-        full_buffer.extend_from_slice(&bytes);
-
-        let response_queue = match wait_for_response {
-            true => {
-                let queue_def = QueueDefinition::builder()
-                    .queue_name("".into())
-                    .exclusive(true)
-                    .auto_delete(true)
-                    .build();
-                let t_queue = &self.create_queue(queue_def).await?;
-                let queue = t_queue.to_owned();
-                Some(queue)
-            }
-            false => None,
-        };
-        println!("Created queue: {response_queue:?}");
-        let properties = PropertiesBuilder::builder()
-            .reply_to(response_queue.clone().unwrap().to_owned())
-            .build();
-
-        // Content header
-        let content_header =
-            content::Content::new(self.channel_id, message.len() as u64, properties);
-        let bytes = encode_frame(&content_header).unwrap();
-        full_buffer.extend_from_slice(&bytes);
-
-        // body
-        let mut message_bytes = Vec::new();
-        message_bytes.extend_from_slice(message.as_bytes());
-        let body = body::Body::new(self.channel_id, RawBytes(message_bytes));
-        let bytes = encode_frame(&body).unwrap();
-        full_buffer.extend_from_slice(&bytes);
-
-        self.connection.write(full_buffer).await;
-
-        if wait_for_response {
-            self.consume_on_queue(&response_queue.unwrap(), handler)
-                .await?;
-        }
+        self.publish_with_options(
+            exchange,
+            routing_key,
+            body,
+            properties,
+            PublishOptions::default(),
+        )
+        .await
+    }
+
+    pub async fn publish_with_options(
+        &mut self,
+        exchange: &str,
+        routing_key: &str,
+        body: &[u8],
+        properties: Properties,
+        options: PublishOptions,
+    ) -> Result<()> {
+        self.connection
+            .basic_publish(exchange, routing_key, body, properties, &options)
+            .await?;
         Ok(())
     }
 
-    pub async fn consume_on_queue(&mut self, queue: &str, handler: Handler) -> Result<()> {
-        let consume = basic::Consume::new(self.channel_id, queue);
-        let bytes = encode_frame(&consume).unwrap();
-        self.connection.write(bytes).await;
-
-        // ConsumeOk
-        let buffer = self.connection.read().await.unwrap();
-        let _consume_ok: basic::ConsumeOk = decode_frame(&buffer).unwrap();
-        let (tx, rx) = mpsc::unbounded_channel();
-        let sender = self.connection.get_writer();
-        let channel_id = self.channel_id.clone();
-        tokio::task::spawn(async move { consumer_task(channel_id, rx, sender, handler).await });
-
-        while let Some(buffer) = self.connection.read().await {
-            let header: Header = decode_frame(&buffer).unwrap();
-            if header.frame_type == FrameType::Heartbeat {
-                let heart_beat = [8_u8, 0, 0, 0, 0, 0, 0, 0xCE].to_vec();
-                self.connection.write(heart_beat).await;
-                println!("Sent heartbeat");
-                continue;
-            } else {
-                let mut frames = buffer.split_inclusive(|&x| x == FRAME_END);
-                let deliver: basic::Deliver = decode_frame(frames.next().unwrap()).unwrap();
-                let delivery_tag = deliver.delivery_tag;
-                let content_header: content::Content =
-                    decode_frame(frames.next().unwrap()).unwrap();
-                let properties = content_header.properties;
-                let body: body::Body = decode_frame(frames.next().unwrap()).unwrap();
-                let bytes = body.content.0;
-                let message = Message::new(bytes, properties, AdditionalInfo::new(delivery_tag));
-                let _x = tx.send(message);
+    pub async fn consume_on_queue(
+        &mut self,
+        queue: &str,
+        handler: Handler,
+    ) -> Result<CancelReason> {
+        self.consume(queue, ConsumeOptions::default(), handler)
+            .await
+    }
+
+    pub async fn start_consumer(
+        &mut self,
+        queue: &str,
+        options: &ConsumeOptions,
+    ) -> Result<Consumer> {
+        match options.prefetch_count {
+            // Per-consumer limit, only applies to consumers started after it is set
+            Some(prefetch_count) => {
+                self.connection
+                    .set_prefetch(prefetch_count, 0, false)
+                    .await?;
+                self.options_prefetch = true;
+            }
+            // The limit of an earlier consumer stays set on the channel
+            None if self.options_prefetch => {
+                self.connection.set_prefetch(0, 0, false).await?;
+                self.options_prefetch = false;
             }
+            None => {}
         }
+        let consumer = self.connection.consume(queue, options).await?;
+        Ok(consumer)
+    }
+
+    // Runs the handler for every delivery, returning once the consumer is cancelled
+    pub async fn consume(
+        &mut self,
+        queue: &str,
+        options: ConsumeOptions,
+        handler: Handler,
+    ) -> Result<CancelReason> {
+        let consumer = self.start_consumer(queue, &options).await?;
+        Ok(consumer.run(handler).await)
+    }
+
+    pub async fn cancel_consumer(&mut self, consumer_tag: &str) -> Result<()> {
+        self.connection.cancel(consumer_tag).await?;
         Ok(())
     }
-}
 
-async fn consumer_task(
-    channel_id: u16,
-    mut receiver: UnboundedReceiver<Message>,
-    sender: UnboundedSender<Bytes>,
-    handler: Handler,
-) {
-    println!("Consumer started");
-    while let Some(message) = receiver.recv().await {
-        let s = sender.clone();
-        tokio::task::spawn(async move {
-            // Auto ack mode before
-            let ack = basic::Ack::new(message.additional_info.delivery_tag);
-            let bytes = encode_frame(ack).unwrap();
-            s.send(bytes).unwrap();
-            println!("Sent ack");
-            let response_queue = message.properties.clone().reply_to;
-            let response = handler(message);
-
-            match response_queue {
-                Some(queue) => {
-                    let message = response.unwrap();
-
-                    let mut full_buffer: Vec<u8> = Vec::new();
-                    let publish = basic::Publish::new(channel_id, "", &queue, false, false);
-                    let bytes = encode_frame(&publish).unwrap();
-                    full_buffer.extend_from_slice(&bytes);
-
-                    let content_header = content::Content::new(
-                        channel_id,
-                        message.len() as u64,
-                        Properties::default(),
-                    );
-                    let bytes = encode_frame(&content_header).unwrap();
-                    full_buffer.extend_from_slice(&bytes);
-
-                    let mut message_bytes = Vec::new();
-                    message_bytes.extend_from_slice(&message);
-                    let body = body::Body::new(channel_id, RawBytes(message_bytes));
-                    let bytes = encode_frame(&body).unwrap();
-                    full_buffer.extend_from_slice(&bytes);
-                    s.send(full_buffer).unwrap();
-                    println!("Sent message!")
+    // Fetches a single message, None if the queue is empty. Unless `no_ack` is set
+    // the message must be acknowledged with `ack`.
+    pub async fn get(&mut self, queue: &str, no_ack: bool) -> Result<Option<Message>> {
+        let message = self.connection.get(queue, no_ack).await?;
+        Ok(message)
+    }
+
+    pub async fn ack(&mut self, delivery_tag: u64) -> Result<()> {
+        self.connection.ack(delivery_tag).await?;
+        Ok(())
+    }
+
+    // Puts the channel in transactional mode, publishes and acks are then only
+    // applied on `tx_commit`. A channel cannot leave transactional mode, but when the
+    // server closes it the reopened channel is no longer transactional.
+    pub async fn tx_select(&mut self) -> Result<()> {
+        self.connection.tx_select().await?;
+        Ok(())
+    }
+
+    pub async fn tx_commit(&mut self) -> Result<()> {
+        self.connection.tx_commit().await?;
+        Ok(())
+    }
+
+    pub async fn tx_rollback(&mut self) -> Result<()> {
+        self.connection.tx_rollback().await?;
+        Ok(())
+    }
+
+    // Runs `f` in a transaction, committing if it returns Ok and rolling back on Err
+    pub async fn transaction<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: AsyncFnOnce(&mut Client) -> Result<T>,
+    {
+        if !self.connection.transactional() {
+            self.tx_select().await?;
+        }
+        match f(self).await {
+            Ok(value) => {
+                self.tx_commit().await?;
+                Ok(value)
+            }
+            Err(e) => {
+                // If the server closed the channel the transaction went with it, and
+                // the reopened channel is not transactional. Either way the caller
+                // gets the error from `f`.
+                if self.connection.transactional() {
+                    let _ = self.tx_rollback().await;
                 }
-                None => {}
+                Err(e)
             }
-        });
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::client_connection::tests::*;
+    use crate::encde::{encode_frame, TransactionMethodId};
+    use crate::frame::{queue, transaction};
+
+    // A client on the channel-backed connection fixture
+    pub(crate) fn client() -> (
+        Client,
+        tokio::sync::mpsc::UnboundedReceiver<Bytes>,
+        tokio::sync::mpsc::UnboundedSender<crate::dispatcher::Reply>,
+    ) {
+        let (connection, written, replies) = connection();
+        let client = Client {
+            connection,
+            channel_id: 1,
+            options_prefetch: false,
+        };
+        (client, written, replies)
+    }
+
+    fn orders() -> QueueDefinition {
+        QueueDefinition::builder()
+            .queue_name("orders".into())
+            .build()
+    }
+
+    fn declare_orders() -> Vec<u8> {
+        encode_frame(queue::Declare::new(
+            1, "orders", false, false, false, false, false,
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commits() {
+        let (mut client, mut written, replies) = client();
+        replies.send(tx_ok(TransactionMethodId::SelectOk)).unwrap();
+        replies.send(tx_ok(TransactionMethodId::CommitOk)).unwrap();
+
+        let value = client.transaction(async |_| Ok(7)).await.unwrap();
+        assert_eq!(value, 7);
+        let select = encode_frame(transaction::Select::new(1)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), select);
+        let commit = encode_frame(transaction::Commit::new(1)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), commit);
+
+        // The channel stays transactional, tx.select is only sent once
+        replies.send(tx_ok(TransactionMethodId::CommitOk)).unwrap();
+        client.transaction(async |_| Ok(())).await.unwrap();
+        assert_eq!(written.try_recv().unwrap(), commit);
+        assert!(written.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back() {
+        let (mut client, mut written, replies) = client();
+        replies.send(tx_ok(TransactionMethodId::SelectOk)).unwrap();
+        // The rollback fails too, the caller still gets the error from `f`
+        replies.send(close(1, 406)).unwrap();
+        replies.send(open_ok(1)).unwrap();
+
+        let result: Result<()> = client
+            .transaction(async |_| Err("handler failed".into()))
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "handler failed");
+        written.try_recv().unwrap();
+        let rollback = encode_frame(transaction::Rollback::new(1)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), rollback);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_on_closed_channel() {
+        let (mut client, mut written, replies) = client();
+        replies.send(tx_ok(TransactionMethodId::SelectOk)).unwrap();
+        replies.send(close(1, 406)).unwrap();
+        replies.send(open_ok(1)).unwrap();
+
+        let result = client
+            .transaction(async |client| client.create_queue(orders()).await)
+            .await;
+        let error = result.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::ChannelClosed {
+                reply_code: 406,
+                ..
+            })
+        ));
+        written.try_recv().unwrap();
+        assert_eq!(written.try_recv().unwrap(), declare_orders());
+        // The channel was reopened, without tx mode and so without a rollback
+        let open = encode_frame(crate::frame::channel::Open::new(1)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), open);
+        assert!(written.try_recv().is_err());
     }
 }
@@ -1,26 +1,45 @@
-use std::sync::atomic::AtomicU16;
-
-use tokio::sync::mpsc::UnboundedSender;
+use bytes::BytesMut;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
 
+use crate::compression;
+use crate::consumer::{Consumer, Responder};
+use crate::dispatcher::{
+    ConsumerEntry, Dispatcher, FlowState, Reply, SharedConsumers, SharedListeners,
+};
 use crate::encde::*;
 use crate::frame::*;
 use crate::tcp::TcpAdapter;
 use crate::types::*;
-use crate::ConnectionParameters;
+use crate::{BlockedBehaviour, ConnectionParameters};
 
-fn get_channel_id() -> u16 {
-    static ID_COUNTER: AtomicU16 = AtomicU16::new(1);
-    ID_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-}
+// Channel ids only have to be unique within a connection, 0 is the connection itself
+const CHANNEL_ID: u16 = 1;
 
 pub struct Connection {
-    tcp_adapter: TcpAdapter,
+    writer: UnboundedSender<Bytes>,
+    replies: UnboundedReceiver<Reply>,
+    consumers: SharedConsumers,
+    listeners: SharedListeners,
+    flow: watch::Receiver<FlowState>,
+    blocked_behaviour: BlockedBehaviour,
+    max_decompressed_size: usize,
+    frame_max: u32,
+    // Reused for every publish, the frames are split off it once encoded
+    encode_buffer: BytesMut,
     pub channel_id: u16,
+    // Opened only for the duration of queue_info, so that the channel.close following
+    // a 404 does not take down `channel_id` with its consumers, qos and tx mode
+    query_channel_id: u16,
+    // Highest channel id the broker accepts, 0 for no limit
+    channel_max: u16,
+    // Whether tx.select was sent on `channel_id`, reset when the channel is reopened
+    transactional: bool,
 }
 
 impl Connection {
-    pub fn get_writer(&self) -> UnboundedSender<Vec<u8>> {
-        self.tcp_adapter.clone_sender()
+    pub fn get_writer(&self) -> UnboundedSender<Bytes> {
+        self.writer.clone()
     }
 
     pub async fn connect(connection_parameters: ConnectionParameters<'_>) -> Self {
@@ -38,14 +57,18 @@ impl Connection {
         let buffer = tcp_adapter.receive().await.unwrap();
         let start: connection::Start = decode_frame(&buffer).unwrap();
 
-        // Write StartOk
+        // Write StartOk, the PLAIN response is "\0username\0password"
+        let response = [
+            b"\0",
+            connection_parameters.username.as_bytes(),
+            b"\0",
+            connection_parameters.password.as_bytes(),
+        ]
+        .concat();
         let start_ok_test = connection::StartOk::new(
             connection_parameters.mechanism.as_str(),
-            &format!(
-                "\0{}\0{}",
-                connection_parameters.username, connection_parameters.password
-            ),
-            &start.locales,
+            &response,
+            start.locales.as_str().unwrap_or("en_US"),
         );
         let bytes = encode_frame(start_ok_test).unwrap();
         tcp_adapter.send(bytes).await;
@@ -66,24 +89,82 @@ impl Connection {
         // OpenOk
         let buffer = tcp_adapter.receive().await.unwrap();
         let _open_ok: connection::OpenOk = decode_frame(&buffer).unwrap();
+
+        // Handshake done, from here on every incoming frame goes through the dispatcher
+        let (writer, receiver) = tcp_adapter.into_parts();
+        let (reply_sender, replies) = mpsc::unbounded_channel();
+        let consumers = SharedConsumers::default();
+        let listeners = SharedListeners::default();
+        let (flow_sender, flow) = watch::channel(FlowState::default());
+        let channel_id = CHANNEL_ID;
+        let mut dispatcher = Dispatcher {
+            channel_id,
+            receiver,
+            writer: writer.clone(),
+            replies: reply_sender,
+            consumers: consumers.clone(),
+            listeners: listeners.clone(),
+            flow: flow_sender,
+        };
+        tokio::task::spawn(async move {
+            dispatcher.start().await;
+        });
+
         Self {
-            tcp_adapter,
-            channel_id: get_channel_id(),
+            writer,
+            replies,
+            consumers,
+            listeners,
+            flow,
+            blocked_behaviour: connection_parameters.blocked_behaviour,
+            max_decompressed_size: connection_parameters.max_decompressed_size,
+            frame_max: tune.frame_max,
+            encode_buffer: BytesMut::new(),
+            channel_id,
+            query_channel_id: channel_id + 1,
+            channel_max: tune.channel_max,
+            transactional: false,
         }
     }
 
     pub async fn create_channel(&mut self) -> Result<u16> {
-        let open = channel::Open::new(self.channel_id);
-        let bytes = encode_frame(&open).unwrap();
+        self.open_channel(self.channel_id).await?;
+        self.transactional = false;
+        Ok(self.channel_id)
+    }
+
+    async fn open_channel(&mut self, channel_id: u16) -> Result<()> {
+        let open = channel::Open::new(channel_id);
+        let bytes = encode_frame(&open)?;
+        self.write(bytes).await;
+
+        // Not read_method, which itself reopens the channel after a close
+        let reply = self.replies.recv().await.ok_or("Connection closed")?;
+        let _open_ok = channel::OpenOk::try_from(AmqpFrame::decode(&reply.frame)?)
+            .map_err(|frame| format!("Unexpected reply {frame:?}"))?;
+        Ok(())
+    }
+
+    async fn close_channel(&mut self, channel_id: u16) -> Result<()> {
+        let close = channel::Close::new(channel_id, 200, "Goodbye", 0, 0);
+        let bytes = encode_frame(close)?;
         self.write(bytes).await;
 
-        let buffer = self.read().await.unwrap();
-        let _open_ok: channel::OpenOk = decode_frame(&buffer).unwrap();
-        Ok(1)
+        let _close_ok: channel::CloseOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub async fn create_queue(&mut self, queue_definition: QueueDefinition) -> Result<QueueInfo> {
+        self.declare_queue(self.channel_id, queue_definition).await
     }
-    pub async fn create_queue(&mut self, queue_definition: QueueDefinition) -> Result<String> {
+
+    async fn declare_queue(
+        &mut self,
+        channel_id: u16,
+        queue_definition: QueueDefinition,
+    ) -> Result<QueueInfo> {
         let declare = queue::Declare::new(
-            self.channel_id,
+            channel_id,
             &queue_definition.queue_name,
             queue_definition.passive,
             queue_definition.durable,
@@ -94,10 +175,46 @@ impl Connection {
         let bytes = encode_frame(declare).unwrap();
         self.write(bytes).await;
 
-        let buffer = self.read().await.unwrap();
-        let declare_ok: queue::DeclareOk = decode_frame(&buffer).unwrap();
-        let ShortString(queue_name) = declare_ok.queue_name;
-        Ok(queue_name)
+        let declare_ok: queue::DeclareOk = self.read_method().await?;
+        let ShortString(name) = declare_ok.queue_name;
+        Ok(QueueInfo {
+            name,
+            message_count: declare_ok.message_count,
+            consumer_count: declare_ok.consumer_count,
+        })
+    }
+
+    // The passive declare runs on its own channel, a missing queue makes the broker
+    // close the channel it was declared on
+    pub async fn queue_info(&mut self, queue_name: &str) -> Result<QueueInfo> {
+        let queue_definition = QueueDefinition::builder()
+            .queue_name(queue_name.into())
+            .passive(true)
+            .build();
+        if self.channel_max != 0 && self.query_channel_id > self.channel_max {
+            return Err(format!(
+                "Channel {} for queue_info exceeds channel_max {}",
+                self.query_channel_id, self.channel_max
+            )
+            .into());
+        }
+        self.open_channel(self.query_channel_id).await?;
+        match self
+            .declare_queue(self.query_channel_id, queue_definition)
+            .await
+        {
+            Ok(queue_info) => {
+                self.close_channel(self.query_channel_id).await?;
+                Ok(queue_info)
+            }
+            Err(error) => match error.downcast_ref::<Error>() {
+                Some(Error::ChannelClosed {
+                    reply_code: NOT_FOUND,
+                    ..
+                }) => Err(Error::QueueNotFound(queue_name.into()).into()),
+                _ => Err(error),
+            },
+        }
     }
 
     pub async fn create_exchange(
@@ -109,25 +226,540 @@ impl Connection {
         let bytes = encode_frame(declare)?;
         self.write(bytes).await;
 
-        let buffer = self.read().await.unwrap();
-        let _declare_ok: exchange::DeclareOk = decode_frame(&buffer).unwrap();
+        let _declare_ok: exchange::DeclareOk = self.read_method().await?;
         Ok(())
     }
 
     pub async fn delete_exchange(&mut self, exchange: &str) -> Result<()> {
-        let delete = exchange::Delete::new(self.channel_id, exchange);
+        let delete = exchange::Delete::new(self.channel_id, exchange, false, false);
         let bytes = encode_frame(delete)?;
         self.write(bytes).await;
 
-        let buffer = self.read().await.unwrap();
-        let _declare_ok: exchange::DeleteOk = decode_frame(&buffer).unwrap();
+        let _declare_ok: exchange::DeleteOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub async fn set_prefetch(&mut self, count: u16, size: u32, global: bool) -> Result<()> {
+        let qos = basic::QualityOfService::new(self.channel_id, size, count, global);
+        let bytes = encode_frame(qos)?;
+        self.write(bytes).await;
+
+        let _qos_ok: basic::QualityOfServiceOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub async fn consume(&mut self, queue: &str, options: &ConsumeOptions) -> Result<Consumer> {
+        let consumer_tag = match (&options.consumer_tag, options.no_wait) {
+            // Without ConsumeOk there is no way to learn a server generated tag
+            (ConsumerTag::Server, true) => ConsumerTag::Generated.resolve(),
+            (consumer_tag, _) => consumer_tag.resolve(),
+        };
+        let consume = basic::Consume::new(
+            self.channel_id,
+            queue,
+            &consumer_tag,
+            options.no_local,
+            options.no_ack,
+            options.exclusive,
+            options.no_wait,
+            options.arguments.clone(),
+        );
+        let bytes = encode_frame(consume)?;
+
+        // Register the consumer before any delivery can arrive for it
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let consumer = ConsumerEntry {
+            queue: queue.into(),
+            no_ack: options.no_ack,
+            sender,
+        };
+        {
+            let mut consumers = self.consumers.lock().unwrap();
+            match options.no_wait {
+                true => _ = consumers.active.insert(consumer_tag.clone(), consumer),
+                false => consumers.pending = Some(consumer),
+            }
+        }
+        self.write(bytes).await;
+
+        let consumer_tag = match options.no_wait {
+            true => consumer_tag,
+            false => {
+                let consume_ok: basic::ConsumeOk = self.read_method().await?;
+                let ShortString(consumer_tag) = consume_ok.consumer_tag;
+                consumer_tag
+            }
+        };
+        let responder = Responder::new(
+            self.channel_id,
+            options.no_ack,
+            self.frame_max,
+            self.get_writer(),
+            self.listeners.clone(),
+            self.flow.clone(),
+            self.blocked_behaviour,
+        );
+        Ok(Consumer::new(
+            consumer_tag,
+            responder,
+            self.max_decompressed_size,
+            receiver,
+        ))
+    }
+
+    pub async fn cancel(&mut self, consumer_tag: &str) -> Result<()> {
+        let cancel = basic::Cancel::new(self.channel_id, consumer_tag, false);
+        let bytes = encode_frame(cancel)?;
+        self.write(bytes).await;
+
+        let _cancel_ok: basic::CancelOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub async fn get(&mut self, queue: &str, no_ack: bool) -> Result<Option<Message>> {
+        let get = basic::Get::new(self.channel_id, queue, no_ack);
+        let bytes = encode_frame(get)?;
+        self.write(bytes).await;
+
+        let reply = self.read_reply().await?;
+        let get_ok = match AmqpFrame::decode(&reply.frame)? {
+            AmqpFrame::Basic(BasicFrame::GetEmpty(_)) => return Ok(None),
+            AmqpFrame::Basic(BasicFrame::GetOk(get_ok)) => get_ok,
+            frame => return Err(format!("Unexpected reply to basic.get {frame:?}").into()),
+        };
+        let (properties, bytes) = match reply.content.ok_or("basic.get-ok without content")? {
+            Ok(content) => content,
+            Err(e) => {
+                // Same as for deliveries, see Dispatcher::dispatch
+                if !no_ack {
+                    let reject = basic::Reject::new(self.channel_id, get_ok.delivery_tag, false);
+                    self.write(encode_frame(reject)?).await;
+                }
+                return Err(e.into());
+            }
+        };
+        let (properties, bytes) =
+            compression::decompress_blocking(properties, bytes, self.max_decompressed_size).await;
+
+        let redelivered = get_ok.redelivered();
+        let ShortString(exchange) = get_ok.exchange_name;
+        let ShortString(routing_key) = get_ok.routing_key;
+        let additional_info = AdditionalInfo {
+            channel_id: self.channel_id,
+            queue: queue.into(),
+            consumer_tag: None,
+            delivery_tag: get_ok.delivery_tag,
+            redelivered,
+            exchange,
+            routing_key,
+            message_count: Some(get_ok.message_count),
+        };
+        Ok(Some(Message::new(bytes, properties, additional_info)))
+    }
+
+    pub async fn tx_select(&mut self) -> Result<()> {
+        let select = transaction::Select::new(self.channel_id);
+        let bytes = encode_frame(select)?;
+        self.write(bytes).await;
+
+        let _select_ok: transaction::SelectOk = self.read_method().await?;
+        self.transactional = true;
+        Ok(())
+    }
+
+    pub fn transactional(&self) -> bool {
+        self.transactional
+    }
+
+    pub async fn tx_commit(&mut self) -> Result<()> {
+        let commit = transaction::Commit::new(self.channel_id);
+        let bytes = encode_frame(commit)?;
+        self.write(bytes).await;
+
+        let _commit_ok: transaction::CommitOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub async fn tx_rollback(&mut self) -> Result<()> {
+        let rollback = transaction::Rollback::new(self.channel_id);
+        let bytes = encode_frame(rollback)?;
+        self.write(bytes).await;
+
+        let _rollback_ok: transaction::RollbackOk = self.read_method().await?;
+        Ok(())
+    }
+
+    pub fn returned_messages(&self) -> UnboundedReceiver<ReturnedMessage> {
+        let (sender, mut returns) = mpsc::unbounded_channel();
+        self.listeners.lock().unwrap().returns = Some(sender);
+        // Decompressed here rather than in the dispatcher, in the order they came in
+        let (forward, receiver) = mpsc::unbounded_channel();
+        let max_size = self.max_decompressed_size;
+        tokio::task::spawn(async move {
+            while let Some(mut returned) = returns.recv().await {
+                (returned.properties, returned.bytes) =
+                    compression::decompress_blocking(returned.properties, returned.bytes, max_size)
+                        .await;
+                if forward.send(returned).is_err() {
+                    break;
+                }
+            }
+        });
+        receiver
+    }
+
+    pub fn connection_events(&self) -> UnboundedReceiver<ConnectionEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.listeners.lock().unwrap().events = Some(sender);
+        receiver
+    }
+
+    pub async fn basic_publish(
+        &mut self,
+        exchange: &str,
+        routing_key: &str,
+        body: &[u8],
+        properties: Properties,
+        options: &PublishOptions,
+    ) -> Result<()> {
+        let (body, properties) = compression::compress(options.compression, body, properties)?;
+        let publish = basic::Publish::new(
+            self.channel_id,
+            exchange,
+            routing_key,
+            options.mandatory,
+            options.immediate,
+        );
+        // Whatever was encoded before a failure must not go out with the next publish
+        if let Err(e) = encode_publish(
+            &mut self.encode_buffer,
+            self.channel_id,
+            self.frame_max,
+            &publish,
+            body,
+            properties,
+        ) {
+            self.encode_buffer.clear();
+            return Err(e);
+        }
+        let full_buffer = self.encode_buffer.split().freeze();
+        self.publish(full_buffer).await
+    }
+
+    // Writes publish frames once the broker allows it, see BlockedBehaviour
+    async fn publish(&mut self, bytes: Bytes) -> Result<()> {
+        wait_to_publish(&mut self.flow, self.blocked_behaviour).await?;
+        self.check_channel().await?;
+        self.write(bytes).await;
+        Ok(())
+    }
+
+    pub async fn ack(&mut self, delivery_tag: u64) -> Result<()> {
+        self.check_channel().await?;
+        let ack = basic::Ack::new(self.channel_id, delivery_tag);
+        let bytes = encode_frame(ack)?;
+        self.write(bytes).await;
         Ok(())
     }
 
-    pub async fn write(&self, bytes: Vec<u8>) {
-        self.tcp_adapter.send(bytes).await;
+    // The broker may close `channel_id` without a synchronous method to answer, e.g. a
+    // publish to a missing exchange. Writing on it then closes the connection with 504,
+    // so the queued channel.close is read instead, which reopens the channel and fails.
+    async fn check_channel(&mut self) -> Result<()> {
+        while self.flow.borrow().closed {
+            // Replies queued ahead of the close belong to abandoned calls
+            self.read_reply().await?;
+        }
+        Ok(())
+    }
+
+    // Reads the reply to a synchronous method, failing on any other method rather
+    // than decoding it into T
+    async fn read_method<T: TryFrom<AmqpFrame, Error = AmqpFrame>>(&mut self) -> Result<T> {
+        let reply = self.read_reply().await?;
+        T::try_from(AmqpFrame::decode(&reply.frame)?)
+            .map_err(|frame| format!("Unexpected reply {frame:?}").into())
+    }
+
+    // Reads the reply to a synchronous method. If the server closed `channel_id` instead,
+    // the channel is reopened before returning the error.
+    async fn read_reply(&mut self) -> Result<Reply> {
+        let reply = self.replies.recv().await.ok_or("Connection closed")?;
+        match AmqpFrame::decode(&reply.frame)? {
+            AmqpFrame::Channel(ChannelFrame::Close(close)) => {
+                let header: Header = decode_frame(&reply.frame)?;
+                if header.channel_id == self.channel_id {
+                    self.create_channel().await?;
+                }
+
+                let ShortString(reply_text) = close.reply_text;
+                Err(Error::ChannelClosed {
+                    reply_code: close.reply_code,
+                    reply_text,
+                }
+                .into())
+            }
+            AmqpFrame::Connection(ConnectionFrame::Close(close)) => {
+                let ShortString(reply_text) = close.reply_text;
+                Err(Error::ConnectionClosed {
+                    reply_code: close.reply_code,
+                    reply_text,
+                }
+                .into())
+            }
+            _ => Ok(reply),
+        }
+    }
+
+    pub async fn write(&self, bytes: impl Into<Bytes>) {
+        self.writer.send(bytes.into()).unwrap();
+    }
+}
+
+// Holds a publish back while the connection is blocked or the channel flow stopped,
+// or fails it with BlockedBehaviour::Fail
+pub(crate) async fn wait_to_publish(
+    flow: &mut watch::Receiver<FlowState>,
+    blocked_behaviour: BlockedBehaviour,
+) -> Result<()> {
+    let blocked_reason = {
+        let state = flow.borrow();
+        match (&state.blocked, state.active) {
+            (Some(reason), _) => Some(reason.clone()),
+            (None, false) => Some("Channel flow stopped".into()),
+            (None, true) => None,
+        }
+    };
+    if let Some(reason) = blocked_reason {
+        match blocked_behaviour {
+            BlockedBehaviour::Fail => return Err(Error::PublishBlocked(reason).into()),
+            BlockedBehaviour::Wait => _ = flow.wait_for(FlowState::can_publish).await?,
+        }
+    }
+    Ok(())
+}
+
+// Encodes basic.publish followed by its content header and body frames
+pub(crate) fn encode_publish(
+    buffer: &mut BytesMut,
+    channel_id: u16,
+    frame_max: u32,
+    publish: &basic::Publish,
+    body: Bytes,
+    properties: Properties,
+) -> Result<()> {
+    encode_frame_into(publish, buffer)?;
+
+    let content_header = content::Content::new(channel_id, body.len() as u64, properties);
+    encode_frame_into(&content_header, buffer)?;
+
+    // Bodies larger than frame_max are split over several frames, 0 means no limit
+    let chunk_size = match frame_max {
+        0 => body.len().max(1),
+        frame_max => frame_max as usize - HEADER_SIZE - 1,
+    };
+    for start in (0..body.len()).step_by(chunk_size) {
+        let chunk = body.slice(start..body.len().min(start + chunk_size));
+        let body = body::Body::new(channel_id, RawBytes(chunk));
+        encode_frame_into(&body, buffer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A connection wired to channels instead of a socket. Frames it writes come out of
+    // the returned receiver, frames sent on the returned sender are read as replies.
+    pub(crate) fn connection() -> (Connection, UnboundedReceiver<Bytes>, UnboundedSender<Reply>) {
+        let (writer, written) = mpsc::unbounded_channel();
+        let (reply_sender, replies) = mpsc::unbounded_channel();
+        let (_, flow) = watch::channel(FlowState::default());
+        let connection = Connection {
+            writer,
+            replies,
+            consumers: SharedConsumers::default(),
+            listeners: SharedListeners::default(),
+            flow,
+            blocked_behaviour: BlockedBehaviour::Fail,
+            max_decompressed_size: compression::DEFAULT_MAX_DECOMPRESSED_SIZE,
+            frame_max: 131072,
+            encode_buffer: BytesMut::new(),
+            channel_id: 1,
+            query_channel_id: 2,
+            channel_max: 2047,
+            transactional: false,
+        };
+        (connection, written, reply_sender)
+    }
+
+    pub(crate) fn method_header(channel_id: u16) -> Header {
+        Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        }
+    }
+
+    pub(crate) fn open_ok(channel_id: u16) -> Reply {
+        let open_ok = (
+            method_header(channel_id),
+            ClassID::Channel,
+            ChannelMethodID::OpenOk,
+            LongString::default(),
+        );
+        Bytes::from(encode_frame(open_ok).unwrap()).into()
+    }
+
+    pub(crate) fn close(channel_id: u16, reply_code: u16) -> Reply {
+        let close = channel::Close::new(channel_id, reply_code, "closed", 50, 10);
+        Bytes::from(encode_frame(close).unwrap()).into()
+    }
+
+    // tx.select-ok, tx.commit-ok or tx.rollback-ok
+    pub(crate) fn tx_ok(method_id: TransactionMethodId) -> Reply {
+        let tx_ok = (method_header(1), ClassID::Transaction, method_id);
+        Bytes::from(encode_frame(tx_ok).unwrap()).into()
+    }
+
+    #[tokio::test]
+    async fn test_failed_publish_leaves_nothing_behind() {
+        let (mut connection, mut written, _replies) = connection();
+        let options = PublishOptions::default();
+
+        // Fails after the frame header, class and method id and exchange are written
+        let routing_key = "k".repeat(256);
+        let result = connection
+            .basic_publish(
+                "events",
+                &routing_key,
+                b"body",
+                Properties::default(),
+                &options,
+            )
+            .await;
+        assert!(result.is_err());
+        // Fails after the whole basic.publish frame is written
+        let properties = Properties::builder()
+            .correlation_id("c".repeat(256))
+            .build();
+        let result = connection
+            .basic_publish("events", "key", b"body", properties, &options)
+            .await;
+        assert!(result.is_err());
+        assert!(written.try_recv().is_err());
+
+        connection
+            .basic_publish("events", "key", b"body", Properties::default(), &options)
+            .await
+            .unwrap();
+        let expected = [
+            encode_frame(basic::Publish::new(1, "events", "key", false, false)).unwrap(),
+            encode_frame(content::Content::new(1, 4, Properties::default())).unwrap(),
+            encode_frame(body::Body::new(1, RawBytes(Bytes::from_static(b"body")))).unwrap(),
+        ]
+        .concat();
+        assert_eq!(written.try_recv().unwrap(), expected);
+        assert!(written.try_recv().is_err());
     }
-    pub async fn read(&mut self) -> Option<Vec<u8>> {
-        self.tcp_adapter.receive().await
+
+    #[tokio::test]
+    async fn test_read_method_checks_the_method() {
+        let (mut connection, _written, replies) = connection();
+
+        // Same class as the expected basic.cancel-ok, but not a reply to it
+        let ack = encode_frame(basic::Ack::new(1, 3)).unwrap();
+        replies.send(Bytes::from(ack).into()).unwrap();
+        assert!(connection.cancel("ctag-1").await.is_err());
+
+        let cancel_ok = encode_frame(basic::CancelOk::new(1, "ctag-1")).unwrap();
+        replies.send(Bytes::from(cancel_ok).into()).unwrap();
+        connection.cancel("ctag-1").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_not_found_leaves_the_channel_open() {
+        let (mut connection, mut written, replies) = connection();
+        replies.send(tx_ok(TransactionMethodId::SelectOk)).unwrap();
+        connection.tx_select().await.unwrap();
+        written.try_recv().unwrap();
+
+        replies.send(open_ok(2)).unwrap();
+        replies.send(close(2, NOT_FOUND)).unwrap();
+        let error = connection.queue_info("missing").await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::QueueNotFound(_))
+        ));
+        assert_eq!(
+            written.try_recv().unwrap(),
+            encode_frame(channel::Open::new(2)).unwrap()
+        );
+        let declare = queue::Declare::new(2, "missing", true, false, false, false, false);
+        assert_eq!(written.try_recv().unwrap(), encode_frame(declare).unwrap());
+        // channel 1 was not reopened
+        assert!(written.try_recv().is_err());
+        assert!(connection.transactional());
+
+        // Closing channel 1 itself reopens it, without tx mode
+        replies.send(close(1, 406)).unwrap();
+        replies.send(open_ok(1)).unwrap();
+        let queue_definition = QueueDefinition::builder()
+            .queue_name("orders".into())
+            .build();
+        assert!(connection.create_queue(queue_definition).await.is_err());
+        written.try_recv().unwrap();
+        assert_eq!(
+            written.try_recv().unwrap(),
+            encode_frame(channel::Open::new(1)).unwrap()
+        );
+        assert!(!connection.transactional());
+    }
+
+    #[tokio::test]
+    async fn test_publish_on_closed_channel_reopens_it() {
+        let (mut connection, mut written, replies) = connection();
+        let (flow_sender, flow) = watch::channel(FlowState::default());
+        connection.flow = flow;
+        let options = PublishOptions::default();
+
+        // A publish to a missing exchange, closed by the broker after it was written
+        flow_sender.send_modify(|state| state.closed = true);
+        replies.send(close(1, NOT_FOUND)).unwrap();
+        replies.send(open_ok(1)).unwrap();
+        let error = connection
+            .basic_publish("missing", "key", b"body", Properties::default(), &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::ChannelClosed {
+                reply_code: NOT_FOUND,
+                ..
+            })
+        ));
+        // Only channel.open was written, not the publish
+        assert_eq!(
+            written.try_recv().unwrap(),
+            encode_frame(channel::Open::new(1)).unwrap()
+        );
+        assert!(written.try_recv().is_err());
+
+        // The dispatcher clears the state on the open-ok
+        flow_sender.send_modify(|state| state.closed = false);
+        connection.ack(3).await.unwrap();
+        assert_eq!(
+            written.try_recv().unwrap(),
+            encode_frame(basic::Ack::new(1, 3)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_info_respects_channel_max() {
+        let (mut connection, mut written, _replies) = connection();
+        connection.channel_max = 1;
+        assert!(connection.queue_info("orders").await.is_err());
+        assert!(written.try_recv().is_err());
     }
 }
@@ -0,0 +1,152 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::*;
+
+// Serialization format for typed payloads, identified on the wire by `content_type`
+pub trait Codec {
+    const CONTENT_TYPE: &'static str;
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
+}
+
+pub struct Json;
+
+impl Codec for Json {
+    const CONTENT_TYPE: &'static str = "application/json";
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Codec for MessagePack {
+    const CONTENT_TYPE: &'static str = "application/msgpack";
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec_named(value)?)
+    }
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+}
+
+#[cfg(feature = "cbor")]
+pub struct Cbor;
+
+#[cfg(feature = "cbor")]
+impl Codec for Cbor {
+    const CONTENT_TYPE: &'static str = "application/cbor";
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes)?;
+        Ok(bytes)
+    }
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        Ok(ciborium::from_reader(bytes)?)
+    }
+}
+
+impl Client {
+    // Serializes the value with the codec and sets `content_type` to match
+    pub async fn publish_as<C: Codec, T: Serialize>(
+        &mut self,
+        exchange: &str,
+        routing_key: &str,
+        value: &T,
+        properties: Properties,
+    ) -> Result<()> {
+        let body = C::encode(value)?;
+        let properties = properties
+            .into_builder()
+            .content_type(C::CONTENT_TYPE.into())
+            .build();
+        self.publish(exchange, routing_key, &body, properties).await
+    }
+
+    pub async fn publish_json<T: Serialize>(
+        &mut self,
+        exchange: &str,
+        routing_key: &str,
+        value: &T,
+        properties: Properties,
+    ) -> Result<()> {
+        self.publish_as::<Json, T>(exchange, routing_key, value, properties)
+            .await
+    }
+}
+
+impl Message {
+    // Deserializes the body with the codec. A message without `content_type` is
+    // accepted, one with a different content type is an error.
+    pub fn decode_as<C: Codec, T: DeserializeOwned>(&self) -> Result<T> {
+        if let Some(content_type) = self.properties.content_type() {
+            if content_type != C::CONTENT_TYPE {
+                return Err(Error::ContentTypeMismatch {
+                    expected: C::CONTENT_TYPE.into(),
+                    actual: content_type.into(),
+                }
+                .into());
+            }
+        }
+        C::decode(&self.bytes)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        self.decode_as::<Json, T>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Event {
+        id: u32,
+        name: String,
+    }
+
+    fn message(bytes: Vec<u8>, content_type: &str) -> Message {
+        let properties = Properties::builder()
+            .content_type(content_type.into())
+            .build();
+        let additional_info = AdditionalInfo {
+            channel_id: 1,
+            queue: "events".into(),
+            consumer_tag: None,
+            delivery_tag: 1,
+            redelivered: false,
+            exchange: "".into(),
+            routing_key: "events".into(),
+            message_count: None,
+        };
+        Message::new(bytes.into(), properties, additional_info)
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let event = Event {
+            id: 1,
+            name: "created".into(),
+        };
+        let bytes = Json::encode(&event).unwrap();
+        let decoded: Event = message(bytes, "application/json").json().unwrap();
+        assert_eq!(decoded, event);
+    }
+
+    #[test]
+    fn test_content_type_mismatch() {
+        let bytes = Json::encode(&1_u32).unwrap();
+        let result = message(bytes, "text/plain").json::<u32>();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use std::io::Read;
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+use std::io::Write;
+
+use crate::*;
+
+// Default for ConnectionParameters::max_decompressed_size
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+// Body compression, identified on the wire by `content_encoding`. Each algorithm
+// is behind a cargo feature of the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    // zlib framed, as in HTTP's "deflate"
+    #[cfg(feature = "deflate")]
+    Deflate,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+// Without any feature the enum is empty and the bodies below are unreachable
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "deflate", feature = "zstd")),
+    allow(unused_variables, unreachable_code)
+)]
+impl Compression {
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => "gzip",
+            #[cfg(feature = "deflate")]
+            Self::Deflate => "deflate",
+            #[cfg(feature = "zstd")]
+            Self::Zstd => "zstd",
+        }
+    }
+
+    // None for encodings without an enabled feature
+    pub fn from_content_encoding(content_encoding: &str) -> Option<Self> {
+        match content_encoding {
+            #[cfg(feature = "gzip")]
+            "gzip" => Some(Self::Gzip),
+            #[cfg(feature = "deflate")]
+            "deflate" => Some(Self::Deflate),
+            #[cfg(feature = "zstd")]
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "deflate")]
+            Self::Deflate => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(bytes)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(zstd::encode_all(bytes, 0)?),
+        }
+    }
+
+    // Fails rather than allocate more than `max_size` bytes, a small body can
+    // decompress to an arbitrary size
+    pub fn decompress(self, bytes: &[u8], max_size: usize) -> Result<Vec<u8>> {
+        let decoder: Box<dyn Read + '_> = match self {
+            #[cfg(feature = "gzip")]
+            Self::Gzip => Box::new(flate2::read::GzDecoder::new(bytes)),
+            #[cfg(feature = "deflate")]
+            Self::Deflate => Box::new(flate2::read::ZlibDecoder::new(bytes)),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::new(bytes)?),
+        };
+        // One byte past the limit tells a body of exactly max_size from a larger one
+        let mut decompressed = Vec::new();
+        decoder
+            .take(max_size as u64 + 1)
+            .read_to_end(&mut decompressed)?;
+        if decompressed.len() > max_size {
+            return Err(format!("Decompressed body exceeds {max_size} bytes").into());
+        }
+        Ok(decompressed)
+    }
+}
+
+// Compresses a body about to be published, overriding any `content_encoding`
+pub(crate) fn compress(
+    compression: Option<Compression>,
+    body: &[u8],
+    properties: Properties,
+) -> Result<(Bytes, Properties)> {
+    match compression {
+        Some(compression) => {
+            let body = compression.compress(body)?;
+            let properties = properties
+                .into_builder()
+                .content_encoding(compression.content_encoding().into())
+                .build();
+            Ok((body.into(), properties))
+        }
+        None => Ok((Bytes::copy_from_slice(body), properties)),
+    }
+}
+
+// Decompresses a received body when its `content_encoding` is recognized, clearing
+// the encoding so the message reads as if it was never compressed. Anything else,
+// including a body that fails to decompress or exceeds `max_size`, is delivered as is.
+pub(crate) fn decompress(
+    mut properties: Properties,
+    bytes: Bytes,
+    max_size: usize,
+) -> (Properties, Bytes) {
+    let Some(compression) = compression(&properties) else {
+        return (properties, bytes);
+    };
+    match compression.decompress(&bytes, max_size) {
+        Ok(decompressed) => {
+            properties.take_content_encoding();
+            (properties, decompressed.into())
+        }
+        // The content_encoding is kept, telling the receiver the body is still compressed
+        Err(_) => (properties, bytes),
+    }
+}
+
+// `decompress` on the blocking pool, so a large body does not stall the task
+// receiving it
+pub(crate) async fn decompress_blocking(
+    properties: Properties,
+    bytes: Bytes,
+    max_size: usize,
+) -> (Properties, Bytes) {
+    if compression(&properties).is_none() {
+        return (properties, bytes);
+    }
+    let task = tokio::task::spawn_blocking(move || decompress(properties, bytes, max_size));
+    match task.await {
+        Ok(decompressed) => decompressed,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+fn compression(properties: &Properties) -> Option<Compression> {
+    properties
+        .content_encoding()
+        .and_then(Compression::from_content_encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_encoding_passes_through() {
+        let properties = Properties::builder().content_encoding("br".into()).build();
+        let (properties, bytes) = decompress(properties, Bytes::from_static(b"body"), 4);
+        assert_eq!(properties.content_encoding(), Some("br"));
+        assert_eq!(bytes, &b"body"[..]);
+    }
+
+    #[cfg(any(feature = "gzip", feature = "deflate", feature = "zstd"))]
+    #[test]
+    fn test_round_trip() {
+        let compressions = [
+            #[cfg(feature = "gzip")]
+            Compression::Gzip,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ];
+        let body = b"event ".repeat(100);
+        for compression in compressions {
+            let (compressed, properties) =
+                compress(Some(compression), &body, Properties::default()).unwrap();
+            assert!(compressed.len() < body.len());
+            assert_eq!(
+                properties.content_encoding(),
+                Some(compression.content_encoding())
+            );
+            let (properties, bytes) = decompress(properties, compressed, body.len());
+            assert_eq!(properties.content_encoding(), None);
+            assert_eq!(bytes, body);
+        }
+    }
+
+    #[cfg(any(feature = "gzip", feature = "deflate", feature = "zstd"))]
+    #[test]
+    fn test_oversized_body_passes_through() {
+        let compressions = [
+            #[cfg(feature = "gzip")]
+            Compression::Gzip,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ];
+        let body = vec![0; 1 << 20];
+        for compression in compressions {
+            let (compressed, properties) =
+                compress(Some(compression), &body, Properties::default()).unwrap();
+            assert!(compression.decompress(&compressed, body.len() - 1).is_err());
+            let (properties, bytes) = decompress(properties, compressed.clone(), 1024);
+            assert_eq!(
+                properties.content_encoding(),
+                Some(compression.content_encoding())
+            );
+            assert_eq!(bytes, compressed);
+        }
+    }
+}
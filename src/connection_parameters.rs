@@ -10,6 +10,14 @@ impl Mechanism {
     }
 }
 
+// What a publish does while the broker has blocked the connection or stopped the channel flow
+#[derive(Debug, Clone, Copy, Default)]
+pub enum BlockedBehaviour {
+    #[default]
+    Wait,
+    Fail,
+}
+
 pub struct ConnectionParameters<'a> {
     pub host: &'a str,
     pub port: u16,
@@ -17,6 +25,10 @@ pub struct ConnectionParameters<'a> {
     pub password: &'a str,
     pub mechanism: Mechanism,
     pub virtual_host: &'a str,
+    pub blocked_behaviour: BlockedBehaviour,
+    // Largest body a compressed message is inflated to, larger ones are delivered
+    // still compressed
+    pub max_decompressed_size: usize,
 }
 
 pub struct ConnectionParametersBuilder<'a> {
@@ -26,6 +38,8 @@ pub struct ConnectionParametersBuilder<'a> {
     password: Option<&'a str>,
     mechanism: Mechanism,
     virtual_host: &'a str,
+    blocked_behaviour: BlockedBehaviour,
+    max_decompressed_size: usize,
 }
 
 impl<'a> ConnectionParametersBuilder<'a> {
@@ -36,7 +50,9 @@ impl<'a> ConnectionParametersBuilder<'a> {
             username: None,
             password: None,
             mechanism: Mechanism::Plain,
-            virtual_host: "/".into(),
+            virtual_host: "/",
+            blocked_behaviour: BlockedBehaviour::default(),
+            max_decompressed_size: crate::compression::DEFAULT_MAX_DECOMPRESSED_SIZE,
         }
     }
     pub fn host(mut self, host: &'a str) -> Self {
@@ -59,6 +75,14 @@ impl<'a> ConnectionParametersBuilder<'a> {
         self.port = port;
         self
     }
+    pub fn blocked_behaviour(mut self, blocked_behaviour: BlockedBehaviour) -> Self {
+        self.blocked_behaviour = blocked_behaviour;
+        self
+    }
+    pub fn max_decompressed_size(mut self, max_decompressed_size: usize) -> Self {
+        self.max_decompressed_size = max_decompressed_size;
+        self
+    }
 
     pub fn build(self) -> ConnectionParameters<'a> {
         ConnectionParameters {
@@ -74,6 +98,8 @@ impl<'a> ConnectionParametersBuilder<'a> {
                 .expect("Must provided `password` in ConnectionParametersBuilder"),
             mechanism: self.mechanism,
             virtual_host: self.virtual_host,
+            blocked_behaviour: self.blocked_behaviour,
+            max_decompressed_size: self.max_decompressed_size,
         }
     }
 }
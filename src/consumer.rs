@@ -0,0 +1,323 @@
+use bytes::BytesMut;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+
+use crate::client_connection::{encode_publish, wait_to_publish};
+use crate::compression;
+use crate::dispatcher::{FlowState, SharedListeners};
+use crate::encde::*;
+use crate::frame::*;
+use crate::rpc;
+use crate::types::*;
+use crate::BlockedBehaviour;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelReason {
+    // Cancelled with `Client::cancel_consumer`
+    Client,
+    // Cancelled by the broker, e.g. the queue was deleted or its leader moved
+    Server,
+    ChannelClosed,
+    ConnectionClosed,
+}
+
+pub enum ConsumerEvent {
+    Delivery(Box<Message>),
+    Cancelled(CancelReason),
+}
+
+pub struct Consumer {
+    pub consumer_tag: String,
+    responder: Responder,
+    max_decompressed_size: usize,
+    receiver: UnboundedReceiver<ConsumerEvent>,
+}
+
+impl Consumer {
+    pub(crate) fn new(
+        consumer_tag: String,
+        responder: Responder,
+        max_decompressed_size: usize,
+        receiver: UnboundedReceiver<ConsumerEvent>,
+    ) -> Self {
+        Self {
+            consumer_tag,
+            responder,
+            max_decompressed_size,
+            receiver,
+        }
+    }
+
+    pub async fn recv(&mut self) -> ConsumerEvent {
+        match self.receiver.recv().await {
+            Some(ConsumerEvent::Delivery(mut message)) => {
+                (message.properties, message.bytes) = compression::decompress_blocking(
+                    message.properties,
+                    message.bytes,
+                    self.max_decompressed_size,
+                )
+                .await;
+                ConsumerEvent::Delivery(message)
+            }
+            Some(event) => event,
+            None => ConsumerEvent::Cancelled(CancelReason::ConnectionClosed),
+        }
+    }
+
+    pub(crate) fn responder(&self) -> Responder {
+        self.responder.clone()
+    }
+
+    // Runs the handler for every delivery until the consumer is cancelled
+    pub async fn run(mut self, handler: Handler) -> CancelReason {
+        loop {
+            match self.recv().await {
+                ConsumerEvent::Delivery(message) => self
+                    .responder
+                    .respond_in_task(*message, |message| handler(message).map(rpc::Reply::new)),
+                ConsumerEvent::Cancelled(reason) => return reason,
+            }
+        }
+    }
+}
+
+// Publishes the replies to a consumer's deliveries and settles them. Both
+// Consumer::run and RpcServer reply through it.
+#[derive(Clone)]
+pub(crate) struct Responder {
+    channel_id: u16,
+    no_ack: bool,
+    frame_max: u32,
+    writer: UnboundedSender<Bytes>,
+    listeners: SharedListeners,
+    // Replies are held back or failed like any other publish
+    flow: watch::Receiver<FlowState>,
+    blocked_behaviour: BlockedBehaviour,
+}
+
+impl Responder {
+    pub(crate) fn new(
+        channel_id: u16,
+        no_ack: bool,
+        frame_max: u32,
+        writer: UnboundedSender<Bytes>,
+        listeners: SharedListeners,
+        flow: watch::Receiver<FlowState>,
+        blocked_behaviour: BlockedBehaviour,
+    ) -> Self {
+        Self {
+            channel_id,
+            no_ack,
+            frame_max,
+            writer,
+            listeners,
+            flow,
+            blocked_behaviour,
+        }
+    }
+
+    // Runs `respond` on a task of its own. A failure is reported as
+    // ConnectionEvent::Error, there is no caller to return it to.
+    pub(crate) fn respond_in_task(
+        &self,
+        message: Message,
+        handler: impl FnOnce(Message) -> Option<rpc::Reply> + Send + 'static,
+    ) {
+        let responder = self.clone();
+        tokio::task::spawn(async move {
+            let delivery_tag = message.additional_info.delivery_tag;
+            if let Err(e) = responder.respond(message, handler).await {
+                let error = format!("Failed to respond to delivery {delivery_tag}: {e}");
+                responder
+                    .listeners
+                    .lock()
+                    .unwrap()
+                    .send_event(ConnectionEvent::Error { error });
+            }
+        });
+    }
+
+    // Runs the handler and publishes its reply to the request's reply_to queue, a reply
+    // to a request without reply_to is dropped. The request is acked once the reply is
+    // written, or nacked without requeue when it cannot be. A reply fails to publish
+    // the same way every time, so as with undecodable deliveries the request goes to
+    // the dead letter exchange, if there is one, rather than back to the queue.
+    // Nothing is written once the broker closed the channel, it requeues the request.
+    pub(crate) async fn respond(
+        &self,
+        message: Message,
+        handler: impl FnOnce(Message) -> Option<rpc::Reply>,
+    ) -> Result<()> {
+        let delivery_tag = message.additional_info.delivery_tag;
+        let request_properties = message.properties.clone();
+        let published = match (request_properties.reply_to(), handler(message)) {
+            (Some(reply_to), Some(reply)) => {
+                self.publish(reply_to, &request_properties, reply).await
+            }
+            _ => Ok(()),
+        };
+        if self.no_ack {
+            return published;
+        }
+        if self.flow.borrow().closed {
+            return published.and(Err("Channel closed".into()));
+        }
+        match published {
+            Ok(()) => {
+                let ack = basic::Ack::new(self.channel_id, delivery_tag);
+                self.writer.send(encode_frame(ack)?.into())?;
+                Ok(())
+            }
+            Err(e) => {
+                let nack = basic::Nack::new(self.channel_id, delivery_tag, false, false);
+                self.writer.send(encode_frame(nack)?.into())?;
+                Err(e)
+            }
+        }
+    }
+
+    async fn publish(&self, reply_to: &str, request: &Properties, reply: rpc::Reply) -> Result<()> {
+        wait_to_publish(&mut self.flow.clone(), self.blocked_behaviour).await?;
+        if self.flow.borrow().closed {
+            return Err("Channel closed".into());
+        }
+        let publish = basic::Publish::new(self.channel_id, "", reply_to, false, false);
+        let properties = rpc::reply_properties(request, reply.properties);
+        let mut buffer = BytesMut::new();
+        encode_publish(
+            &mut buffer,
+            self.channel_id,
+            self.frame_max,
+            &publish,
+            reply.body.into(),
+            properties,
+        )?;
+        self.writer.send(buffer.freeze())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn request(delivery_tag: u64, reply_to: &str) -> Message {
+        let properties = Properties::builder()
+            .reply_to(reply_to.into())
+            .correlation_id("call-1".into())
+            .build();
+        let additional_info = AdditionalInfo {
+            channel_id: 1,
+            queue: "requests".into(),
+            consumer_tag: Some("ctag-1".into()),
+            delivery_tag,
+            redelivered: false,
+            exchange: "".into(),
+            routing_key: "requests".into(),
+            message_count: None,
+        };
+        Message::new(Bytes::from_static(b"ping"), properties, additional_info)
+    }
+
+    fn responder(
+        flow: watch::Receiver<FlowState>,
+        blocked_behaviour: BlockedBehaviour,
+    ) -> (Responder, UnboundedReceiver<Bytes>) {
+        let (writer, written) = mpsc::unbounded_channel();
+        let responder = Responder {
+            channel_id: 1,
+            no_ack: false,
+            frame_max: 16,
+            writer,
+            listeners: SharedListeners::default(),
+            flow,
+            blocked_behaviour,
+        };
+        (responder, written)
+    }
+
+    // Without a timestamp, which Properties::default() would set
+    fn reply() -> Option<rpc::Reply> {
+        let properties = Properties::builder().build();
+        let body = b"pong, split over frames".to_vec();
+        Some(rpc::Reply::with_properties(body, properties))
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let (_, flow) = watch::channel(FlowState::default());
+        let (responder, mut written) = responder(flow, BlockedBehaviour::Fail);
+
+        responder
+            .respond(request(1, "replies"), |_| reply())
+            .await
+            .unwrap();
+        let properties = Properties::builder()
+            .correlation_id("call-1".into())
+            .build();
+        let mut expected = BytesMut::new();
+        let publish = basic::Publish::new(1, "", "replies", false, false);
+        let body = Bytes::from_static(b"pong, split over frames");
+        encode_publish(&mut expected, 1, 16, &publish, body, properties).unwrap();
+        assert_eq!(written.try_recv().unwrap(), expected);
+        let ack = encode_frame(basic::Ack::new(1, 1)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), ack);
+
+        // A reply_to that does not fit a short string cannot be published
+        let reply_to = "r".repeat(256);
+        assert!(responder
+            .respond(request(2, &reply_to), |_| reply())
+            .await
+            .is_err());
+        let nack = encode_frame(basic::Nack::new(1, 2, false, false)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), nack);
+        assert!(written.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_respond_waits_while_blocked() {
+        let (flow_sender, flow) = watch::channel(FlowState::default());
+        flow_sender.send_modify(|state| state.blocked = Some("low on memory".into()));
+        let (responder, mut written) = responder(flow, BlockedBehaviour::Wait);
+
+        let respond = tokio::task::spawn(async move {
+            let result = responder.respond(request(1, "replies"), |_| reply()).await;
+            result.is_ok()
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(written.try_recv().is_err());
+
+        flow_sender.send_modify(|state| state.blocked = None);
+        assert!(respond.await.unwrap());
+        let publish = basic::Publish::new(1, "", "replies", false, false);
+        assert!(written
+            .recv()
+            .await
+            .unwrap()
+            .starts_with(&encode_frame(publish).unwrap()));
+        let ack = encode_frame(basic::Ack::new(1, 1)).unwrap();
+        assert_eq!(written.recv().await.unwrap(), ack);
+    }
+
+    #[tokio::test]
+    async fn test_respond_fails_while_blocked() {
+        let (flow_sender, flow) = watch::channel(FlowState::default());
+        flow_sender.send_modify(|state| state.active = false);
+        let (responder, mut written) = responder(flow, BlockedBehaviour::Fail);
+
+        let error = responder
+            .respond(request(1, "replies"), |_| reply())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::PublishBlocked(_))
+        ));
+        // Not published, and nacked so that it is not redelivered forever
+        let nack = encode_frame(basic::Nack::new(1, 1, false, false)).unwrap();
+        assert_eq!(written.try_recv().unwrap(), nack);
+        assert!(written.try_recv().is_err());
+    }
+}
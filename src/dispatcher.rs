@@ -0,0 +1,691 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::watch;
+
+use crate::consumer::{CancelReason, ConsumerEvent};
+use crate::encde::*;
+use crate::frame::*;
+use crate::types::*;
+
+pub struct ConsumerEntry {
+    pub queue: String,
+    pub no_ack: bool,
+    pub sender: UnboundedSender<ConsumerEvent>,
+}
+
+#[derive(Default)]
+pub struct Consumers {
+    // Registered before basic.consume is sent, moved to `active` once ConsumeOk names the tag
+    pub pending: Option<ConsumerEntry>,
+    pub active: HashMap<String, ConsumerEntry>,
+}
+
+impl Consumers {
+    fn cancel(&mut self, consumer_tag: &str, reason: CancelReason) {
+        if let Some(consumer) = self.active.remove(consumer_tag) {
+            let _ = consumer.sender.send(ConsumerEvent::Cancelled(reason));
+        }
+    }
+
+    fn cancel_all(&mut self, reason: CancelReason) {
+        for (_, consumer) in self.active.drain() {
+            let _ = consumer
+                .sender
+                .send(ConsumerEvent::Cancelled(reason.clone()));
+        }
+    }
+}
+
+pub type SharedConsumers = Arc<Mutex<Consumers>>;
+
+#[derive(Default)]
+pub struct Listeners {
+    pub returns: Option<UnboundedSender<ReturnedMessage>>,
+    pub events: Option<UnboundedSender<ConnectionEvent>>,
+}
+
+impl Listeners {
+    pub(crate) fn send_event(&self, event: ConnectionEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+}
+
+pub type SharedListeners = Arc<Mutex<Listeners>>;
+
+// A reply to a synchronous method, forwarded to the connection
+pub struct Reply {
+    pub frame: Bytes,
+    // The content following basic.get-ok, or why it could not be read
+    pub content: Option<std::result::Result<(Properties, Bytes), String>>,
+}
+
+impl From<Bytes> for Reply {
+    fn from(frame: Bytes) -> Self {
+        Self {
+            frame,
+            content: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FlowState {
+    pub blocked: Option<String>,
+    pub active: bool,
+    // Set when the broker closes the consumer channel, until it is reopened
+    pub closed: bool,
+}
+
+impl FlowState {
+    pub fn can_publish(&self) -> bool {
+        self.blocked.is_none() && self.active
+    }
+}
+
+impl Default for FlowState {
+    fn default() -> Self {
+        Self {
+            blocked: None,
+            active: true,
+            closed: false,
+        }
+    }
+}
+
+// Reads every incoming frame, answering heartbeats and routing deliveries and
+// notifications. Anything else is a reply to a synchronous method and is
+// forwarded to the connection.
+pub struct Dispatcher {
+    // The channel consumers run on, any other channel is a short lived one
+    pub channel_id: u16,
+    pub receiver: UnboundedReceiver<Bytes>,
+    pub writer: UnboundedSender<Bytes>,
+    pub replies: UnboundedSender<Reply>,
+    pub consumers: SharedConsumers,
+    pub listeners: SharedListeners,
+    pub flow: watch::Sender<FlowState>,
+}
+
+impl Dispatcher {
+    pub async fn start(&mut self) {
+        while let Some(buffer) = self.next_frame().await {
+            if let Err(e) = self.dispatch(buffer).await {
+                let error = format!("Error dispatching frame: {e}");
+                self.listeners
+                    .lock()
+                    .unwrap()
+                    .send_event(ConnectionEvent::Error { error });
+            }
+        }
+        self.consumers
+            .lock()
+            .unwrap()
+            .cancel_all(CancelReason::ConnectionClosed);
+    }
+
+    async fn next_frame(&mut self) -> Option<Bytes> {
+        while let Some(buffer) = self.receiver.recv().await {
+            let header: Header = decode_frame(&buffer).ok()?;
+            if header.frame_type == FrameType::Heartbeat {
+                let _ = self.writer.send(Bytes::from_static(&HEARTBEAT));
+                continue;
+            }
+            return Some(buffer);
+        }
+        None
+    }
+
+    // Reads the content header and body frames following a basic.deliver, basic.return
+    // or basic.get-ok. Compressed bodies are left to the receiving side. When the
+    // properties fail to decode the body frames are still read, so they are not taken
+    // for frames of their own.
+    async fn read_content(&mut self) -> Result<(Properties, Bytes)> {
+        let buffer = self.next_frame().await.ok_or("Connection closed")?;
+        let size = content::Content::body_size(&buffer)?;
+        let content_header = AmqpFrame::decode(&buffer);
+        let mut payloads = Vec::new();
+        let mut received = 0;
+        while received < size {
+            let buffer = self.next_frame().await.ok_or("Connection closed")?;
+            let payload = body::Body::payload(&buffer)?;
+            received += payload.len() as u64;
+            payloads.push(payload);
+        }
+        let AmqpFrame::Content(content_header) = content_header? else {
+            return Err("Expected a content header frame".into());
+        };
+        Ok((content_header.properties, body::Body::join(payloads)))
+    }
+
+    async fn dispatch(&mut self, buffer: Bytes) -> Result<()> {
+        let header: Header = decode_frame(&buffer)?;
+        match AmqpFrame::decode(&buffer)? {
+            AmqpFrame::Basic(BasicFrame::Deliver(deliver)) => {
+                let content = self.read_content().await;
+                let consumers = self.consumers.lock().unwrap();
+                let Some(consumer) = consumers.active.get(deliver.consumer_tag.as_str()) else {
+                    return Err(format!(
+                        "Delivery for unknown consumer {:?}",
+                        deliver.consumer_tag
+                    )
+                    .into());
+                };
+                let (properties, bytes) = match content {
+                    Ok(content) => content,
+                    Err(e) => {
+                        // Reject rather than hold the prefetch slot until the channel
+                        // closes. Requeueing would only fail again, with a dead letter
+                        // exchange the message ends up there instead.
+                        if !consumer.no_ack {
+                            let reject =
+                                basic::Reject::new(header.channel_id, deliver.delivery_tag, false);
+                            self.writer.send(encode_frame(reject)?.into())?;
+                        }
+                        return Err(format!(
+                            "Failed to read delivery {}: {e}",
+                            deliver.delivery_tag
+                        )
+                        .into());
+                    }
+                };
+                let redelivered = deliver.redelivered();
+                let ShortString(consumer_tag) = deliver.consumer_tag;
+                let ShortString(exchange) = deliver.exchange;
+                let ShortString(routing_key) = deliver.routing_key;
+                let additional_info = AdditionalInfo {
+                    channel_id: header.channel_id,
+                    queue: consumer.queue.clone(),
+                    consumer_tag: Some(consumer_tag),
+                    delivery_tag: deliver.delivery_tag,
+                    redelivered,
+                    exchange,
+                    routing_key,
+                    message_count: None,
+                };
+                let message = Message::new(bytes, properties, additional_info);
+                let _ = consumer
+                    .sender
+                    .send(ConsumerEvent::Delivery(Box::new(message)));
+            }
+            AmqpFrame::Basic(BasicFrame::Return(basic_return)) => {
+                let (properties, bytes) = self.read_content().await?;
+                let ShortString(reply_text) = basic_return.reply_text;
+                let ShortString(exchange) = basic_return.exchange_name;
+                let ShortString(routing_key) = basic_return.routing_key;
+                let returned = ReturnedMessage {
+                    reply_code: basic_return.reply_code,
+                    reply_text,
+                    exchange,
+                    routing_key,
+                    bytes,
+                    properties,
+                };
+                // Dropped when nobody listens for returns
+                if let Some(returns) = &self.listeners.lock().unwrap().returns {
+                    let _ = returns.send(returned);
+                }
+            }
+            AmqpFrame::Basic(BasicFrame::GetOk(_)) => {
+                let content = self.read_content().await.map_err(|e| e.to_string());
+                self.replies.send(Reply {
+                    frame: buffer,
+                    content: Some(content),
+                })?;
+            }
+            AmqpFrame::Basic(BasicFrame::ConsumeOk(consume_ok)) => {
+                let mut consumers = self.consumers.lock().unwrap();
+                if let Some(consumer) = consumers.pending.take() {
+                    let ShortString(consumer_tag) = consume_ok.consumer_tag;
+                    consumers.active.insert(consumer_tag, consumer);
+                }
+                drop(consumers);
+                self.replies.send(buffer.into())?;
+            }
+            AmqpFrame::Basic(BasicFrame::Cancel(cancel)) => {
+                // Sent by the broker when it cancels a consumer (consumer_cancel_notify)
+                self.consumers
+                    .lock()
+                    .unwrap()
+                    .cancel(&cancel.consumer_tag, CancelReason::Server);
+                if !cancel.no_wait() {
+                    let cancel_ok = basic::CancelOk::new(header.channel_id, &cancel.consumer_tag);
+                    self.writer.send(encode_frame(cancel_ok)?.into())?;
+                }
+            }
+            AmqpFrame::Basic(BasicFrame::CancelOk(cancel_ok)) => {
+                self.consumers
+                    .lock()
+                    .unwrap()
+                    .cancel(&cancel_ok.consumer_tag, CancelReason::Client);
+                self.replies.send(buffer.into())?;
+            }
+            AmqpFrame::Channel(ChannelFrame::Flow(flow)) => {
+                let active = flow.active();
+                self.flow.send_modify(|state| state.active = active);
+                let flow_ok = channel::FlowOk::new(header.channel_id, active);
+                self.writer.send(encode_frame(flow_ok)?.into())?;
+                self.listeners
+                    .lock()
+                    .unwrap()
+                    .send_event(ConnectionEvent::Flow { active });
+            }
+            AmqpFrame::Connection(ConnectionFrame::Blocked(blocked)) => {
+                let ShortString(reason) = blocked.reason;
+                self.flow
+                    .send_modify(|state| state.blocked = Some(reason.clone()));
+                self.listeners
+                    .lock()
+                    .unwrap()
+                    .send_event(ConnectionEvent::Blocked { reason });
+            }
+            AmqpFrame::Connection(ConnectionFrame::Unblocked(_)) => {
+                self.flow.send_modify(|state| state.blocked = None);
+                self.listeners
+                    .lock()
+                    .unwrap()
+                    .send_event(ConnectionEvent::Unblocked);
+            }
+            AmqpFrame::Channel(ChannelFrame::OpenOk(_)) => {
+                // A reopened channel starts with flow active
+                if header.channel_id == self.channel_id {
+                    self.flow.send_modify(|state| {
+                        state.active = true;
+                        state.closed = false;
+                    });
+                }
+                self.replies.send(buffer.into())?;
+            }
+            AmqpFrame::Channel(ChannelFrame::Close(_)) => {
+                if header.channel_id == self.channel_id {
+                    self.flow.send_modify(|state| state.closed = true);
+                    self.consumers
+                        .lock()
+                        .unwrap()
+                        .cancel_all(CancelReason::ChannelClosed);
+                }
+                let close_ok = channel::CloseOk::new(header.channel_id);
+                self.writer.send(encode_frame(close_ok)?.into())?;
+                self.replies.send(buffer.into())?;
+            }
+            AmqpFrame::Connection(ConnectionFrame::Close(_)) => {
+                self.consumers
+                    .lock()
+                    .unwrap()
+                    .cancel_all(CancelReason::ConnectionClosed);
+                let close_ok = connection::CloseOk::new();
+                self.writer.send(encode_frame(close_ok)?.into())?;
+                self.replies.send(buffer.into())?;
+            }
+            // Publisher confirms are never enabled, so these do not answer any request
+            frame @ AmqpFrame::Basic(BasicFrame::Ack(_) | BasicFrame::Nack(_)) => {
+                return Err(format!("Unexpected {frame:?}").into())
+            }
+            AmqpFrame::Content(_) | AmqpFrame::Body(_) | AmqpFrame::Heartbeat => {
+                return Err(format!("Unexpected {:?} frame", header.frame_type).into())
+            }
+            _ => self.replies.send(buffer.into())?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    pub(crate) struct Peer {
+        pub incoming: UnboundedSender<Bytes>,
+        pub written: UnboundedReceiver<Bytes>,
+        pub replies: UnboundedReceiver<Reply>,
+        pub listeners: SharedListeners,
+        pub flow: watch::Receiver<FlowState>,
+    }
+
+    // Runs a dispatcher on channels instead of a socket, until `incoming` is dropped
+    pub(crate) fn dispatcher(consumers: SharedConsumers) -> Peer {
+        let (incoming, receiver) = mpsc::unbounded_channel();
+        let (writer, written) = mpsc::unbounded_channel();
+        let (reply_sender, replies) = mpsc::unbounded_channel();
+        let (flow, flow_receiver) = watch::channel(FlowState::default());
+        let listeners = SharedListeners::default();
+        let mut dispatcher = Dispatcher {
+            channel_id: 1,
+            receiver,
+            writer,
+            replies: reply_sender,
+            consumers,
+            listeners: listeners.clone(),
+            flow,
+        };
+        tokio::task::spawn(async move { dispatcher.start().await });
+        Peer {
+            incoming,
+            written,
+            replies,
+            listeners,
+            flow: flow_receiver,
+        }
+    }
+
+    fn method_header() -> Header {
+        Header {
+            frame_type: FrameType::Method,
+            channel_id: 1,
+            size: 0,
+        }
+    }
+
+    pub(crate) fn get_ok(delivery_tag: u64) -> Bytes {
+        let get_ok = (
+            method_header(),
+            ClassID::Basic,
+            BasicMethodID::GetOk,
+            delivery_tag,
+            Bits([false]),
+            ShortString::from("events"),
+            ShortString::from("order.created"),
+            0_u32,
+        );
+        encode_frame(get_ok).unwrap().into()
+    }
+
+    fn body(content: &'static [u8]) -> Bytes {
+        let body = body::Body::new(1, RawBytes(Bytes::from_static(content)));
+        encode_frame(body).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_get_ok_content() {
+        let mut peer = dispatcher(SharedConsumers::default());
+        let properties = Properties::builder().message_id("msg-1".into()).build();
+        let frames = [
+            get_ok(7),
+            encode_frame(content::Content::new(1, 11, properties))
+                .unwrap()
+                .into(),
+            Bytes::from_static(&HEARTBEAT),
+            body(b"hello "),
+            body(b"world"),
+        ];
+        for frame in frames {
+            peer.incoming.send(frame).unwrap();
+        }
+
+        let reply = peer.replies.recv().await.unwrap();
+        assert_eq!(reply.frame, get_ok(7));
+        let (properties, bytes) = reply.content.unwrap().unwrap();
+        assert_eq!(properties.message_id(), Some("msg-1"));
+        assert_eq!(bytes, &b"hello world"[..]);
+        // The heartbeat in between was answered rather than taken for a body
+        assert_eq!(peer.written.recv().await.unwrap(), &HEARTBEAT[..]);
+    }
+
+    fn deliver(delivery_tag: u64) -> Bytes {
+        let deliver = (
+            method_header(),
+            ClassID::Basic,
+            BasicMethodID::Deliver,
+            ShortString::from("ctag-1"),
+            delivery_tag,
+            Bits([false]),
+            ShortString::from("events"),
+            ShortString::from("order.created"),
+        );
+        encode_frame(deliver).unwrap().into()
+    }
+
+    // A content header whose headers hold a field of the unknown type 'Z'
+    fn undecodable_header(body_size: u64) -> Bytes {
+        let content_header = (
+            Header {
+                frame_type: FrameType::Header,
+                channel_id: 1,
+                size: 0,
+            },
+            ClassID::Basic,
+            0_u16,
+            body_size,
+            0x2000_u16,
+            RawBytes(Bytes::from_static(&[0, 0, 0, 3, 1, b'k', b'Z'])),
+        );
+        encode_frame(content_header).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_delivery_is_rejected() {
+        let consumers = SharedConsumers::default();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let consumer = ConsumerEntry {
+            queue: "orders".into(),
+            no_ack: false,
+            sender,
+        };
+        consumers
+            .lock()
+            .unwrap()
+            .active
+            .insert("ctag-1".into(), consumer);
+        let mut peer = dispatcher(consumers);
+
+        let frames = [
+            deliver(5),
+            undecodable_header(5),
+            body(b"first"),
+            deliver(6),
+            encode_frame(content::Content::new(1, 6, Properties::default()))
+                .unwrap()
+                .into(),
+            body(b"second"),
+        ];
+        for frame in frames {
+            peer.incoming.send(frame).unwrap();
+        }
+
+        let reject = encode_frame(basic::Reject::new(1, 5, false)).unwrap();
+        assert_eq!(peer.written.recv().await.unwrap(), reject);
+        let ConsumerEvent::Delivery(message) = receiver.recv().await.unwrap() else {
+            panic!("Expected a delivery");
+        };
+        assert_eq!(message.additional_info.delivery_tag, 6);
+        assert_eq!(message.bytes, &b"second"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_errors_are_reported() {
+        let peer = dispatcher(SharedConsumers::default());
+        let (sender, mut events) = mpsc::unbounded_channel();
+        peer.listeners.lock().unwrap().events = Some(sender);
+
+        peer.incoming.send(deliver(1)).unwrap();
+        peer.incoming
+            .send(
+                encode_frame(content::Content::new(1, 0, Properties::default()))
+                    .unwrap()
+                    .into(),
+            )
+            .unwrap();
+        let Some(ConnectionEvent::Error { error }) = events.recv().await else {
+            panic!("Expected an error event");
+        };
+        assert!(error.contains("unknown consumer"), "{error}");
+    }
+
+    fn basic_return(routing_key: &str) -> Bytes {
+        let basic_return = (
+            method_header(),
+            ClassID::Basic,
+            BasicMethodID::Return,
+            312_u16,
+            ShortString::from("NO_ROUTE"),
+            ShortString::from("events"),
+            ShortString::from(routing_key),
+        );
+        encode_frame(basic_return).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_returned_messages() {
+        let peer = dispatcher(SharedConsumers::default());
+        let (sender, mut returns) = mpsc::unbounded_channel();
+        let (events_sender, mut events) = mpsc::unbounded_channel();
+        {
+            let mut listeners = peer.listeners.lock().unwrap();
+            listeners.returns = Some(sender);
+            listeners.events = Some(events_sender);
+        }
+
+        let properties = Properties::builder().message_id("msg-1".into()).build();
+        let frames = [
+            basic_return("order.created"),
+            encode_frame(content::Content::new(1, 11, properties))
+                .unwrap()
+                .into(),
+            body(b"hello "),
+            body(b"world"),
+            basic_return("order.updated"),
+            undecodable_header(5),
+            body(b"first"),
+            basic_return("order.deleted"),
+            encode_frame(content::Content::new(1, 6, Properties::default()))
+                .unwrap()
+                .into(),
+            body(b"second"),
+        ];
+        for frame in frames {
+            peer.incoming.send(frame).unwrap();
+        }
+
+        let returned = returns.recv().await.unwrap();
+        assert_eq!(returned.reply_code, 312);
+        assert_eq!(returned.reply_text, "NO_ROUTE");
+        assert_eq!(returned.exchange, "events");
+        assert_eq!(returned.routing_key, "order.created");
+        assert_eq!(returned.properties.message_id(), Some("msg-1"));
+        assert_eq!(returned.bytes, &b"hello world"[..]);
+        // The return with undecodable properties is reported and its body skipped
+        let Some(ConnectionEvent::Error { .. }) = events.recv().await else {
+            panic!("Expected an error event");
+        };
+        let returned = returns.recv().await.unwrap();
+        assert_eq!(returned.routing_key, "order.deleted");
+        assert_eq!(returned.bytes, &b"second"[..]);
+    }
+
+    fn consumer(consumer_tag: &str) -> (SharedConsumers, UnboundedReceiver<ConsumerEvent>) {
+        let consumers = SharedConsumers::default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let consumer = ConsumerEntry {
+            queue: "orders".into(),
+            no_ack: false,
+            sender,
+        };
+        consumers
+            .lock()
+            .unwrap()
+            .active
+            .insert(consumer_tag.into(), consumer);
+        (consumers, receiver)
+    }
+
+    fn cancel(consumer_tag: &str, no_wait: bool) -> Bytes {
+        let cancel = (
+            method_header(),
+            ClassID::Basic,
+            BasicMethodID::Cancel,
+            ShortString::from(consumer_tag),
+            Bits([no_wait]),
+        );
+        encode_frame(cancel).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_server_cancel() {
+        let (consumers, mut receiver) = consumer("ctag-1");
+        let mut peer = dispatcher(consumers.clone());
+
+        peer.incoming.send(cancel("ctag-1", false)).unwrap();
+        let Some(ConsumerEvent::Cancelled(CancelReason::Server)) = receiver.recv().await else {
+            panic!("Expected a server cancel");
+        };
+        let cancel_ok = encode_frame(basic::CancelOk::new(1, "ctag-1")).unwrap();
+        assert_eq!(peer.written.recv().await.unwrap(), cancel_ok);
+        assert!(consumers.lock().unwrap().active.is_empty());
+
+        // No cancel-ok is expected for no_wait
+        let (consumers, mut receiver) = consumer("ctag-2");
+        let mut peer = dispatcher(consumers);
+        peer.incoming.send(cancel("ctag-2", true)).unwrap();
+        let Some(ConsumerEvent::Cancelled(CancelReason::Server)) = receiver.recv().await else {
+            panic!("Expected a server cancel");
+        };
+        drop(peer.incoming);
+        assert!(peer.written.recv().await.is_none());
+    }
+
+    fn channel_close() -> Bytes {
+        encode_frame(channel::Close::new(1, 404, "NOT_FOUND", 60, 40))
+            .unwrap()
+            .into()
+    }
+
+    fn open_ok() -> Bytes {
+        let open_ok = (
+            method_header(),
+            ClassID::Channel,
+            ChannelMethodID::OpenOk,
+            LongString::default(),
+        );
+        encode_frame(open_ok).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn test_channel_close_is_recorded() {
+        let mut peer = dispatcher(SharedConsumers::default());
+
+        peer.incoming.send(channel_close()).unwrap();
+        assert_eq!(peer.replies.recv().await.unwrap().frame, channel_close());
+        assert!(peer.flow.borrow().closed);
+        let close_ok = encode_frame(channel::CloseOk::new(1)).unwrap();
+        assert_eq!(peer.written.recv().await.unwrap(), close_ok);
+
+        peer.incoming.send(open_ok()).unwrap();
+        assert_eq!(peer.replies.recv().await.unwrap().frame, open_ok());
+        assert!(!peer.flow.borrow().closed);
+    }
+
+    #[tokio::test]
+    async fn test_channel_flow() {
+        let mut peer = dispatcher(SharedConsumers::default());
+        let (sender, mut events) = mpsc::unbounded_channel();
+        peer.listeners.lock().unwrap().events = Some(sender);
+
+        for active in [false, true] {
+            let flow = (
+                method_header(),
+                ClassID::Channel,
+                ChannelMethodID::Flow,
+                Bits([active]),
+            );
+            peer.incoming
+                .send(encode_frame(flow).unwrap().into())
+                .unwrap();
+            let flow_ok = encode_frame(channel::FlowOk::new(1, active)).unwrap();
+            assert_eq!(peer.written.recv().await.unwrap(), flow_ok);
+            assert_eq!(peer.flow.borrow().active, active);
+            let Some(ConnectionEvent::Flow {
+                active: event_active,
+            }) = events.recv().await
+            else {
+                panic!("Expected a flow event");
+            };
+            assert_eq!(event_active, active);
+        }
+    }
+}
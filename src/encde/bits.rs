@@ -1,64 +1,61 @@
-#[derive(Debug, Clone, Default)]
-pub struct Bits(pub Vec<u8>);
+// A run of N consecutive bit fields, packed into a single octet with the first field
+// in the lowest bit. No method has more than five consecutive bits, so N is at most 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bits<const N: usize>(pub [bool; N]);
 
-impl From<bool> for Bits {
+impl<const N: usize> Default for Bits<N> {
+    fn default() -> Self {
+        Self([false; N])
+    }
+}
+
+impl From<bool> for Bits<1> {
     fn from(value: bool) -> Self {
-        Self(vec![value as u8])
+        Self([value])
     }
 }
-impl From<(bool,)> for Bits {
+impl From<(bool,)> for Bits<1> {
     fn from(value: (bool,)) -> Self {
-        Self(vec![value.0 as u8])
+        Self([value.0])
     }
 }
-impl From<(bool, bool)> for Bits {
+impl From<(bool, bool)> for Bits<2> {
     fn from(value: (bool, bool)) -> Self {
-        Self(vec![value.0 as u8, value.1 as u8])
+        Self([value.0, value.1])
     }
 }
-impl From<(bool, bool, bool)> for Bits {
+impl From<(bool, bool, bool)> for Bits<3> {
     fn from(value: (bool, bool, bool)) -> Self {
-        Self(vec![value.0 as u8, value.1 as u8, value.2 as u8])
+        Self([value.0, value.1, value.2])
     }
 }
-impl From<(bool, bool, bool, bool)> for Bits {
+impl From<(bool, bool, bool, bool)> for Bits<4> {
     fn from(value: (bool, bool, bool, bool)) -> Self {
-        Self(vec![
-            value.0 as u8,
-            value.1 as u8,
-            value.2 as u8,
-            value.3 as u8,
-        ])
+        Self([value.0, value.1, value.2, value.3])
     }
 }
-impl From<(bool, bool, bool, bool, bool)> for Bits {
+impl From<(bool, bool, bool, bool, bool)> for Bits<5> {
     fn from(value: (bool, bool, bool, bool, bool)) -> Self {
-        Self(vec![
-            value.0 as u8,
-            value.1 as u8,
-            value.2 as u8,
-            value.3 as u8,
-            value.4 as u8,
-        ])
+        Self([value.0, value.1, value.2, value.3, value.4])
     }
 }
 
-impl std::ops::Deref for Bits {
-    type Target = Vec<u8>;
+impl<const N: usize> std::ops::Deref for Bits<N> {
+    type Target = [bool; N];
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl bincode::Encode for Bits {
+impl<const N: usize> bincode::Encode for Bits<N> {
     fn encode<E: bincode::enc::Encoder>(
         &self,
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
         let mut bit_buffer: u8 = 0b0000_0000;
         for (index, flag) in self.iter().enumerate() {
-            if flag == &1 {
+            if *flag {
                 bit_buffer |= 1 << index
             }
         }
@@ -67,17 +64,39 @@ impl bincode::Encode for Bits {
     }
 }
 
-impl bincode::Decode for Bits {
+// Bits past the N named fields are reserved and ignored
+impl<const N: usize> bincode::Decode for Bits<N> {
     fn decode<D: bincode::de::Decoder>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
         let flags = u8::decode(decoder)?;
-        let mut bits = Vec::new();
-        for i in 0_u8..8 {
-            bits.push(flags & 1 << i);
-        }
-        Ok(Self(bits))
+        Ok(Self(std::array::from_fn(|index| flags & (1 << index) != 0)))
+    }
+}
+
+impl<'de, const N: usize> bincode::BorrowDecode<'de> for Bits<N> {
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        bincode::Decode::decode(decoder)
     }
 }
 
-bincode::impl_borrow_decode!(Bits);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encde::{decode_frame, encode_frame_static};
+
+    #[test]
+    fn test_bits_packing() {
+        let bits: Bits<5> = (false, true, false, true, false).into();
+        assert_eq!(encode_frame_static(bits).unwrap(), [0b0000_1010]);
+
+        let bits: Bits<5> = decode_frame(&[0b0000_1010]).unwrap();
+        assert_eq!(bits, Bits([false, true, false, true, false]));
+
+        // Reserved high bits do not leak into the named fields
+        let bits: Bits<2> = decode_frame(&[0b1111_1110]).unwrap();
+        assert_eq!(bits, Bits([false, true]));
+    }
+}
@@ -1,10 +1,11 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ClassID {
     Connection,
     Channel,
     Exchange,
     Queue,
     Basic,
+    Confirm,
     Transaction,
 }
 
@@ -19,6 +20,7 @@ impl bincode::Encode for ClassID {
             ClassID::Exchange => 40_u16.encode(encoder)?,
             ClassID::Queue => 50_u16.encode(encoder)?,
             ClassID::Basic => 60_u16.encode(encoder)?,
+            ClassID::Confirm => 85_u16.encode(encoder)?,
             ClassID::Transaction => 90_u16.encode(encoder)?,
         }
         Ok(())
@@ -35,8 +37,13 @@ impl bincode::Decode for ClassID {
             40_u16 => ClassID::Exchange,
             50_u16 => ClassID::Queue,
             60_u16 => ClassID::Basic,
+            85_u16 => ClassID::Confirm,
             90_u16 => ClassID::Transaction,
-            _ => todo!(),
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown class id {id}"
+                )))
+            }
         })
     }
 }
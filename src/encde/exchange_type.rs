@@ -1,6 +1,6 @@
 use super::ShortString;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExchangeType {
     Direct,
     Fanout,
@@ -23,3 +23,23 @@ impl bincode::Encode for ExchangeType {
         Ok(())
     }
 }
+
+impl bincode::Decode for ExchangeType {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let ShortString(val) = ShortString::decode(decoder)?;
+        Ok(match val.as_str() {
+            "direct" => ExchangeType::Direct,
+            "fanout" => ExchangeType::Fanout,
+            "headers" => ExchangeType::Headers,
+            "topic" => ExchangeType::Topic,
+            _ => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown exchange type {val}"
+                )))
+            }
+        })
+    }
+}
+bincode::impl_borrow_decode!(ExchangeType);
@@ -14,7 +14,7 @@ impl bincode::Encode for FrameType {
     ) -> Result<(), bincode::error::EncodeError> {
         match self {
             FrameType::Body => 3.encode(encoder)?,
-            FrameType::FatalError => todo!(),
+            FrameType::FatalError => return Err(fatal_error()),
             FrameType::Header => 2.encode(encoder)?,
             FrameType::Heartbeat => 8.encode(encoder)?,
             FrameType::Method => 1.encode(encoder)?,
@@ -23,7 +23,12 @@ impl bincode::Encode for FrameType {
     }
 }
 
-#[derive(Debug, Clone)]
+// FatalError stands in for any unknown frame type, there is nothing to write back
+fn fatal_error() -> bincode::error::EncodeError {
+    bincode::error::EncodeError::OtherString("Unknown frame types can not be encoded".into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Header {
     pub frame_type: FrameType,
     pub channel_id: u16,
@@ -40,7 +45,7 @@ impl bincode::Encode for Header {
             FrameType::Header => 0x02_u8.encode(encoder)?,
             FrameType::Body => 0x03_u8.encode(encoder)?,
             FrameType::Heartbeat => 0x08_u8.encode(encoder)?,
-            FrameType::FatalError => todo!(""),
+            FrameType::FatalError => return Err(fatal_error()),
         }
         self.channel_id.encode(encoder)?;
         self.size.encode(encoder)?;
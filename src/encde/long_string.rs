@@ -1,34 +1,59 @@
-#[derive(Debug, Clone)]
-pub struct LongString(pub String);
+use bincode::enc::write::Writer;
+
+// Long strings are arbitrary bytes on the wire, e.g. a SASL response or a binary
+// header value, most of them happen to be UTF-8 text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LongString(pub Vec<u8>);
+
+impl LongString {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // None when the bytes are not valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+}
 
 impl From<&str> for LongString {
     fn from(value: &str) -> Self {
         LongString(value.into())
     }
 }
+impl From<String> for LongString {
+    fn from(value: String) -> Self {
+        LongString(value.into_bytes())
+    }
+}
+impl From<&[u8]> for LongString {
+    fn from(value: &[u8]) -> Self {
+        LongString(value.to_vec())
+    }
+}
+impl From<Vec<u8>> for LongString {
+    fn from(value: Vec<u8>) -> Self {
+        LongString(value)
+    }
+}
 
 impl std::ops::Deref for LongString {
-    type Target = String;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
+
 impl bincode::Encode for LongString {
     fn encode<E: bincode::enc::Encoder>(
         &self,
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
         let LongString(inner) = self;
-        let length = (inner.len() as u32).to_be_bytes();
-        for x in length {
-            x.encode(encoder)?;
-        }
-        // length.encode(encoder)?;
-        for c in inner.chars() {
-            (c as u8).encode(encoder)?;
-        }
-        Ok(())
+        let length = long_string_length(inner)?;
+        length.encode(encoder)?;
+        encoder.writer().write(inner)
     }
 }
 
@@ -37,14 +62,36 @@ impl bincode::Decode for LongString {
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
         let length = u32::decode(decoder)?;
-        let mut string_bytes = vec![];
-        for _ in 0..length {
-            let byte = u8::decode(decoder)?;
-            string_bytes.push(byte);
-        }
-        let decoded_string = String::from_utf8(string_bytes).expect("Fatal Decode Error");
-        Ok(Self(decoded_string))
+        let bytes = super::read_bytes(decoder, length as usize)?;
+        Ok(Self(bytes))
     }
 }
 
 bincode::impl_borrow_decode!(LongString);
+
+pub(crate) fn long_string_length(value: &[u8]) -> Result<u32, bincode::error::EncodeError> {
+    u32::try_from(value.len()).map_err(|_| {
+        bincode::error::EncodeError::OtherString(format!(
+            "Long string of {} bytes exceeds {}",
+            value.len(),
+            u32::MAX
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encde::{decode_frame, encode_frame_static};
+
+    #[test]
+    fn test_binary_round_trip() {
+        let original = LongString(vec![0x00, 0xFF, 0xC3, 0x28, b'a']);
+        let encoded = encode_frame_static(&original).unwrap();
+        assert_eq!(encoded[..4], 5_u32.to_be_bytes());
+        let decoded: LongString = decode_frame(&encoded).unwrap();
+        assert_eq!(decoded.as_bytes(), original.as_bytes());
+        assert_eq!(decoded.as_str(), None);
+        assert_eq!(LongString::from("text").as_str(), Some("text"));
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone)]
+use super::ClassID;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConnectionMethodID {
     Start,
     StartOk,
@@ -10,6 +12,8 @@ pub enum ConnectionMethodID {
     OpenOk,
     Close,
     CloseOk,
+    Blocked,
+    Unblocked,
 }
 
 impl bincode::Decode for ConnectionMethodID {
@@ -27,7 +31,13 @@ impl bincode::Decode for ConnectionMethodID {
             41 => Self::OpenOk,
             50 => Self::Close,
             51 => Self::CloseOk,
-            _ => todo!(),
+            60 => Self::Blocked,
+            61 => Self::Unblocked,
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -48,12 +58,14 @@ impl bincode::Encode for ConnectionMethodID {
             ConnectionMethodID::OpenOk => 41_u16.encode(encoder)?,
             ConnectionMethodID::Close => 50_u16.encode(encoder)?,
             ConnectionMethodID::CloseOk => 51_u16.encode(encoder)?,
+            ConnectionMethodID::Blocked => 60_u16.encode(encoder)?,
+            ConnectionMethodID::Unblocked => 61_u16.encode(encoder)?,
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChannelMethodID {
     Open,
     OpenOk,
@@ -74,7 +86,11 @@ impl bincode::Decode for ChannelMethodID {
             21 => Self::FlowOk,
             40 => Self::Close,
             41 => Self::CloseOk,
-            _ => todo!(),
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -96,7 +112,7 @@ impl bincode::Encode for ChannelMethodID {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExchangeMethodID {
     Declare,
     DeclareOk,
@@ -119,10 +135,14 @@ impl bincode::Decode for ExchangeMethodID {
             20 => Self::Delete,
             21 => Self::DeleteOk,
             30 => Self::Bind,
-            31 => Self::Bind,
+            31 => Self::BindOk,
             40 => Self::Unbind,
             41 => Self::UnbindOk,
-            _ => todo!(),
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -146,7 +166,7 @@ impl bincode::Encode for ExchangeMethodID {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum QueueMethodID {
     Declare,
     DeclareOk,
@@ -175,7 +195,11 @@ impl bincode::Decode for QueueMethodID {
             41 => Self::DeleteOk,
             50 => Self::Unbind,
             51 => Self::UnbindOk,
-            _ => todo!(),
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -200,7 +224,7 @@ impl bincode::Encode for QueueMethodID {
         Ok(())
     }
 }
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BasicMethodID {
     QualityOfService,
     QualityOfServiceOk,
@@ -218,6 +242,7 @@ pub enum BasicMethodID {
     Reject,
     Recover,
     RecoverOk,
+    Nack,
 }
 
 impl bincode::Decode for BasicMethodID {
@@ -241,7 +266,12 @@ impl bincode::Decode for BasicMethodID {
             90 => Self::Reject,
             110 => Self::Recover,
             111 => Self::RecoverOk,
-            _ => todo!(),
+            120 => Self::Nack,
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -268,12 +298,48 @@ impl bincode::Encode for BasicMethodID {
             Self::Reject => 90_u16.encode(encoder)?,
             Self::Recover => 110_u16.encode(encoder)?,
             Self::RecoverOk => 111_u16.encode(encoder)?,
+            Self::Nack => 120_u16.encode(encoder)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmMethodID {
+    Select,
+    SelectOk,
+}
+
+impl bincode::Decode for ConfirmMethodID {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match u16::decode(decoder)? {
+            10 => Self::Select,
+            11 => Self::SelectOk,
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
+        })
+    }
+}
+
+impl bincode::Encode for ConfirmMethodID {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        match self {
+            Self::Select => 10_u16.encode(encoder)?,
+            Self::SelectOk => 11_u16.encode(encoder)?,
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TransactionMethodId {
     Select,
     SelectOk,
@@ -293,7 +359,11 @@ impl bincode::Decode for TransactionMethodId {
             21 => Self::CommitOk,
             30 => Self::Rollback,
             31 => Self::RollbackOk,
-            _ => todo!(),
+            id => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown method id {id}"
+                )))
+            }
         })
     }
 }
@@ -315,9 +385,40 @@ impl bincode::Encode for TransactionMethodId {
     }
 }
 
+// Class and method id pair, used to identify an incoming method frame before
+// decoding it into a concrete frame struct.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Method {
+    Connection(ConnectionMethodID),
+    Channel(ChannelMethodID),
+    Exchange(ExchangeMethodID),
+    Queue(QueueMethodID),
+    Basic(BasicMethodID),
+    Confirm(ConfirmMethodID),
+    Transaction(TransactionMethodId),
+}
+
+impl bincode::Decode for Method {
+    fn decode<D: bincode::de::Decoder>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match ClassID::decode(decoder)? {
+            ClassID::Connection => Self::Connection(ConnectionMethodID::decode(decoder)?),
+            ClassID::Channel => Self::Channel(ChannelMethodID::decode(decoder)?),
+            ClassID::Exchange => Self::Exchange(ExchangeMethodID::decode(decoder)?),
+            ClassID::Queue => Self::Queue(QueueMethodID::decode(decoder)?),
+            ClassID::Basic => Self::Basic(BasicMethodID::decode(decoder)?),
+            ClassID::Confirm => Self::Confirm(ConfirmMethodID::decode(decoder)?),
+            ClassID::Transaction => Self::Transaction(TransactionMethodId::decode(decoder)?),
+        })
+    }
+}
+
 bincode::impl_borrow_decode!(ConnectionMethodID);
 bincode::impl_borrow_decode!(ChannelMethodID);
 bincode::impl_borrow_decode!(QueueMethodID);
 bincode::impl_borrow_decode!(BasicMethodID);
 bincode::impl_borrow_decode!(ExchangeMethodID);
+bincode::impl_borrow_decode!(ConfirmMethodID);
 bincode::impl_borrow_decode!(TransactionMethodId);
+bincode::impl_borrow_decode!(Method);
@@ -1,3 +1,6 @@
+use bincode::de::read::Reader;
+use bytes::{BufMut, BytesMut};
+
 pub mod bits;
 pub mod class;
 pub mod exchange_type;
@@ -7,7 +10,11 @@ pub mod method;
 pub mod properties;
 pub mod raw_bytes;
 pub mod short_string;
+#[cfg(test)]
+pub(crate) mod strategies;
 pub mod table;
+#[cfg(feature = "serde")]
+pub mod table_serde;
 
 pub use bits::Bits;
 pub use class::ClassID;
@@ -15,22 +22,23 @@ pub use exchange_type::ExchangeType;
 pub use header::{FrameType, Header};
 pub use long_string::LongString;
 pub use method::{
-    BasicMethodID, ChannelMethodID, ConnectionMethodID, ExchangeMethodID, QueueMethodID,
-    TransactionMethodId,
+    BasicMethodID, ChannelMethodID, ConfirmMethodID, ConnectionMethodID, ExchangeMethodID, Method,
+    QueueMethodID, TransactionMethodId,
 };
 pub use properties::Properties;
 pub use raw_bytes::RawBytes;
 pub use short_string::ShortString;
-pub use table::{Field, Table};
+pub use table::{Field, Table, TableBuilder};
 
 const CONFIG: bincode::config::Configuration<bincode::config::BigEndian, bincode::config::Fixint> =
     bincode::config::standard()
         .with_big_endian()
         .with_fixed_int_encoding();
-const HEADER_SIZE: usize = 7;
+pub const HEADER_SIZE: usize = 7;
 const SIZE_RANGE: std::ops::Range<usize> = 3..7;
 
 pub const FRAME_END: u8 = 0xCE;
+pub const HEARTBEAT: [u8; 8] = [8, 0, 0, 0, 0, 0, 0, FRAME_END];
 pub const RESERVED8: u8 = 0_u8;
 pub const RESERVED16: u16 = 0_u16;
 
@@ -43,6 +51,33 @@ pub fn encode_frame<E: bincode::enc::Encode>(
     bytes.push(FRAME_END);
     Ok(bytes)
 }
+// Encodes a frame onto the end of a reusable buffer, e.g. to write a publish and
+// its content frames without allocating for each of them. On error the buffer is
+// left as it was, without part of the frame.
+pub fn encode_frame_into<E: bincode::enc::Encode>(
+    val: E,
+    buffer: &mut BytesMut,
+) -> Result<(), bincode::error::EncodeError> {
+    let start = buffer.len();
+    if let Err(e) = bincode::encode_into_writer(&val, BytesMutWriter(buffer), CONFIG) {
+        buffer.truncate(start);
+        return Err(e);
+    }
+    let frame_length = ((buffer.len() - start - HEADER_SIZE) as u32).to_be_bytes();
+    buffer[start + SIZE_RANGE.start..start + SIZE_RANGE.end].copy_from_slice(&frame_length);
+    buffer.put_u8(FRAME_END);
+    Ok(())
+}
+
+struct BytesMutWriter<'a>(&'a mut BytesMut);
+
+impl bincode::enc::write::Writer for BytesMutWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> Result<(), bincode::error::EncodeError> {
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
 pub fn encode_frame_static<E: bincode::enc::Encode>(
     val: E,
 ) -> Result<Vec<u8>, bincode::error::EncodeError> {
@@ -50,6 +85,22 @@ pub fn encode_frame_static<E: bincode::enc::Encode>(
     Ok(bytes)
 }
 
+// Reads a length prefixed value in chunks, so a bogus length fails once the input
+// runs out instead of allocating it all upfront
+pub(crate) fn read_bytes<D: bincode::de::Decoder>(
+    decoder: &mut D,
+    length: usize,
+) -> Result<Vec<u8>, bincode::error::DecodeError> {
+    const CHUNK_SIZE: usize = 4096;
+    let mut bytes = Vec::with_capacity(length.min(CHUNK_SIZE));
+    while bytes.len() < length {
+        let start = bytes.len();
+        bytes.resize(length.min(start + CHUNK_SIZE), 0);
+        decoder.reader().read(&mut bytes[start..])?;
+    }
+    Ok(bytes)
+}
+
 pub fn decode_frame<D: bincode::de::Decode>(src: &[u8]) -> Result<D, bincode::error::DecodeError> {
     let (result, _size): (D, usize) = bincode::decode_from_slice(src, CONFIG)?;
     Ok(result)
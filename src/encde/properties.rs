@@ -1,6 +1,6 @@
 use super::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeliveryMode {
     Persistent,
     NonPersistent,
@@ -25,20 +25,24 @@ impl bincode::Decode for DeliveryMode {
         Ok(match u8::decode(decoder)? {
             1 => Self::NonPersistent,
             2 => Self::Persistent,
-            _ => todo!(),
+            delivery_mode => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Invalid delivery mode {delivery_mode}"
+                )))
+            }
         })
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Properties {
     content_type: Option<String>,
     content_encoding: Option<String>,
-    headers: Option<Vec<(String, Field)>>,
+    headers: Option<Table>,
     delivery_mode: Option<DeliveryMode>,
     priority: Option<u8>,
     correlation_id: Option<String>,
-    pub reply_to: Option<String>,
+    reply_to: Option<String>,
     expiration: Option<String>,
     message_id: Option<String>,
     timestamp: Option<u64>,
@@ -90,10 +94,80 @@ impl Properties {
     }
 }
 
+impl Properties {
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+    pub fn content_encoding(&self) -> Option<&str> {
+        self.content_encoding.as_deref()
+    }
+    pub fn headers(&self) -> Option<&Table> {
+        self.headers.as_ref()
+    }
+    pub fn delivery_mode(&self) -> Option<&DeliveryMode> {
+        self.delivery_mode.as_ref()
+    }
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+    pub fn reply_to(&self) -> Option<&str> {
+        self.reply_to.as_deref()
+    }
+    pub fn expiration(&self) -> Option<&str> {
+        self.expiration.as_deref()
+    }
+    pub fn message_id(&self) -> Option<&str> {
+        self.message_id.as_deref()
+    }
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+    pub fn message_type(&self) -> Option<&str> {
+        self.message_type.as_deref()
+    }
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+    pub fn app_id(&self) -> Option<&str> {
+        self.app_id.as_deref()
+    }
+    pub fn cluster_id(&self) -> Option<&str> {
+        self.cluster_id.as_deref()
+    }
+
+    // Starts a builder from these properties, e.g. to change a received message
+    // before republishing it
+    pub fn into_builder(self) -> PropertiesBuilder {
+        PropertiesBuilder {
+            content_type: self.content_type,
+            content_encoding: self.content_encoding,
+            headers: self.headers,
+            delivery_mode: self.delivery_mode,
+            priority: self.priority,
+            correlation_id: self.correlation_id,
+            reply_to: self.reply_to,
+            expiration: self.expiration,
+            message_id: self.message_id,
+            timestamp: self.timestamp,
+            message_type: self.message_type,
+            user_id: self.user_id,
+            app_id: self.app_id,
+            cluster_id: self.cluster_id,
+        }
+    }
+
+    pub(crate) fn take_content_encoding(&mut self) -> Option<String> {
+        self.content_encoding.take()
+    }
+}
+
 pub struct PropertiesBuilder {
     content_type: Option<String>,
     content_encoding: Option<String>,
-    headers: Option<Vec<(String, Field)>>,
+    headers: Option<Table>,
     delivery_mode: Option<DeliveryMode>,
     priority: Option<u8>,
     correlation_id: Option<String>,
@@ -134,10 +208,17 @@ impl PropertiesBuilder {
         self.content_encoding = Some(content_encoding);
         self
     }
-    pub fn headers(mut self, headers: Vec<(String, Field)>) -> Self {
+    pub fn headers(mut self, headers: Table) -> Self {
         self.headers = Some(headers);
         self
     }
+    // Adds or replaces a single header
+    pub fn header(mut self, key: &str, value: impl Into<Field>) -> Self {
+        self.headers
+            .get_or_insert_with(Table::default)
+            .insert(key, value);
+        self
+    }
     pub fn delivery_mode(mut self, delivery_mode: DeliveryMode) -> Self {
         self.delivery_mode = Some(delivery_mode);
         self
@@ -237,10 +318,7 @@ impl bincode::Decode for Properties {
             false => None,
         };
         let headers = match (flags & HEADERS) != 0 {
-            true => {
-                let Table(val) = Table::decode(decoder)?;
-                Some(val)
-            }
+            true => Some(Table::decode(decoder)?),
             false => None,
         };
         let delivery_mode = match (flags & DELIVERY_MODE) != 0 {
@@ -393,7 +471,6 @@ impl bincode::Encode for Properties {
             val.encode(encoder)?;
         }
         if let Some(val) = &self.headers {
-            let val = Table(val.clone());
             val.encode(encoder)?;
         }
         if let Some(val) = &self.delivery_mode {
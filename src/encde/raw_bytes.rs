@@ -1,8 +1,12 @@
-#[derive(Debug, Clone)]
-pub struct RawBytes(pub Vec<u8>);
+use bincode::de::read::Reader;
+use bincode::enc::write::Writer;
+use bytes::Bytes;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawBytes(pub Bytes);
 
 impl std::ops::Deref for RawBytes {
-    type Target = Vec<u8>;
+    type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -14,23 +18,23 @@ impl bincode::Encode for RawBytes {
         &self,
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
-        for byte in self.iter() {
-            byte.encode(encoder)?;
-        }
-        Ok(())
+        encoder.writer().write(&self.0)
     }
 }
 
+// The decoder has no notion of the remaining length, so this reads to the end of the
+// frame. Received bodies are sliced out of the frame instead, see `body::Body::payload`.
 impl bincode::Decode for RawBytes {
     fn decode<D: bincode::de::Decoder>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
         let mut bytes = Vec::new();
-        while let Ok(byte) = u8::decode(decoder) {
-            bytes.push(byte);
+        let mut byte = [0_u8];
+        while decoder.reader().read(&mut byte).is_ok() {
+            bytes.push(byte[0]);
         }
         _ = bytes.pop(); // remove 0xCE
-        Ok(Self(bytes))
+        Ok(Self(bytes.into()))
     }
 }
 bincode::impl_borrow_decode!(RawBytes);
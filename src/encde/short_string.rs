@@ -1,4 +1,6 @@
-#[derive(Debug, Clone)]
+use bincode::enc::write::Writer;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ShortString(pub String);
 
 impl From<&str> for ShortString {
@@ -19,12 +21,9 @@ impl bincode::Encode for ShortString {
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
         let ShortString(inner) = self;
-        let length = inner.len() as u8;
+        let length = short_string_length(inner)?;
         length.encode(encoder)?;
-        for c in inner.chars() {
-            (c as u8).encode(encoder)?;
-        }
-        Ok(())
+        encoder.writer().write(inner.as_bytes())
     }
 }
 
@@ -32,15 +31,46 @@ impl bincode::Decode for ShortString {
     fn decode<D: bincode::de::Decoder>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
-        // Take exact, u8 then into vec and so on
         let length = u8::decode(decoder)?;
-        let mut string_bytes = vec![];
-        for _ in 0..length {
-            let byte = u8::decode(decoder)?;
-            string_bytes.push(byte);
-        }
-        let decoded_string = String::from_utf8(string_bytes).expect("Fatal Decode Error");
+        let string_bytes = super::read_bytes(decoder, length as usize)?;
+        let decoded_string =
+            String::from_utf8(string_bytes).map_err(|e| bincode::error::DecodeError::Utf8 {
+                inner: e.utf8_error(),
+            })?;
         Ok(Self(decoded_string))
     }
 }
 bincode::impl_borrow_decode!(ShortString);
+
+// Short strings carry a one octet length, so anything over 255 bytes of UTF-8
+// cannot be encoded rather than being truncated
+pub(crate) fn short_string_length(value: &str) -> Result<u8, bincode::error::EncodeError> {
+    u8::try_from(value.len()).map_err(|_| {
+        bincode::error::EncodeError::OtherString(format!(
+            "Short string of {} bytes exceeds 255: {:.32}...",
+            value.len(),
+            value
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encde::{decode_frame, encode_frame_static};
+
+    #[test]
+    fn test_utf8_round_trip() {
+        let original = ShortString("clé.übung.日本".into());
+        let encoded = encode_frame_static(&original).unwrap();
+        assert_eq!(encoded[0] as usize, original.len());
+        let decoded: ShortString = decode_frame(&encoded).unwrap();
+        assert_eq!(decoded.0, original.0);
+    }
+
+    #[test]
+    fn test_too_long() {
+        assert!(encode_frame_static(ShortString("é".repeat(128))).is_err());
+        assert!(encode_frame_static(ShortString("a".repeat(255))).is_ok());
+    }
+}
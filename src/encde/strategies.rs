@@ -0,0 +1,237 @@
+// Proptest strategies for the encde types, shared with the frame round trip tests
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+
+use super::properties::DeliveryMode;
+use super::*;
+
+// Up to 60 chars of at most 4 bytes each, so always within the 255 byte limit
+pub(crate) fn string() -> impl Strategy<Value = String> {
+    ".{0,60}"
+}
+
+pub(crate) fn short_string() -> impl Strategy<Value = ShortString> {
+    string().prop_map(ShortString)
+}
+
+pub(crate) fn long_string() -> impl Strategy<Value = LongString> {
+    prop::collection::vec(any::<u8>(), 0..256).prop_map(LongString)
+}
+
+pub(crate) fn bits<const N: usize>() -> impl Strategy<Value = Bits<N>> {
+    prop::array::uniform(any::<bool>()).prop_map(Bits)
+}
+
+// NaN is left out by `any`, so every field compares equal to itself
+pub(crate) fn field() -> impl Strategy<Value = Field> {
+    let leaf = prop_oneof![
+        short_string().prop_map(Field::SS),
+        long_string().prop_map(Field::LS),
+        any::<bool>().prop_map(Field::Bool),
+        any::<i8>().prop_map(Field::I8),
+        any::<u8>().prop_map(Field::U8),
+        any::<i16>().prop_map(Field::I16),
+        any::<u16>().prop_map(Field::U16),
+        any::<i32>().prop_map(Field::I32),
+        any::<u32>().prop_map(Field::U32),
+        any::<i64>().prop_map(Field::I64),
+        any::<f32>().prop_map(Field::F32),
+        any::<f64>().prop_map(Field::F64),
+        (any::<u8>(), any::<u32>()).prop_map(|(scale, value)| Field::Decimal { scale, value }),
+        any::<u64>().prop_map(Field::Timestamp),
+        prop::collection::vec(any::<u8>(), 0..64).prop_map(Field::ByteArray),
+        Just(Field::Void),
+    ];
+    leaf.prop_recursive(3, 32, 8, |inner| {
+        prop_oneof![
+            prop::collection::vec((string(), inner.clone()), 0..8)
+                .prop_map(|entries| Field::T(Table(entries))),
+            prop::collection::vec(inner, 0..8).prop_map(Field::A),
+        ]
+    })
+}
+
+pub(crate) fn table() -> impl Strategy<Value = Table> {
+    prop::collection::vec((string(), field()), 0..8).prop_map(Table)
+}
+
+pub(crate) fn delivery_mode() -> impl Strategy<Value = DeliveryMode> {
+    prop_oneof![
+        Just(DeliveryMode::Persistent),
+        Just(DeliveryMode::NonPersistent)
+    ]
+}
+
+pub(crate) fn exchange_type() -> impl Strategy<Value = ExchangeType> {
+    prop_oneof![
+        Just(ExchangeType::Direct),
+        Just(ExchangeType::Fanout),
+        Just(ExchangeType::Headers),
+        Just(ExchangeType::Topic),
+    ]
+}
+
+pub(crate) fn properties() -> impl Strategy<Value = Properties> {
+    use prop::option::of;
+    let first = (
+        of(string()),
+        of(string()),
+        of(table()),
+        of(delivery_mode()),
+        of(any::<u8>()),
+        of(string()),
+        of(string()),
+    );
+    let second = (
+        of(string()),
+        of(string()),
+        of(any::<u64>()),
+        of(string()),
+        of(string()),
+        of(string()),
+        of(string()),
+    );
+    (first, second).prop_map(|(first, second)| {
+        let mut builder = Properties::builder();
+        let (
+            content_type,
+            content_encoding,
+            headers,
+            delivery_mode,
+            priority,
+            correlation_id,
+            reply_to,
+        ) = first;
+        let (expiration, message_id, timestamp, message_type, user_id, app_id, cluster_id) = second;
+        if let Some(value) = content_type {
+            builder = builder.content_type(value);
+        }
+        if let Some(value) = content_encoding {
+            builder = builder.content_encoding(value);
+        }
+        if let Some(value) = headers {
+            builder = builder.headers(value);
+        }
+        if let Some(value) = delivery_mode {
+            builder = builder.delivery_mode(value);
+        }
+        if let Some(value) = priority {
+            builder = builder.priority(value);
+        }
+        if let Some(value) = correlation_id {
+            builder = builder.correlation_id(value);
+        }
+        if let Some(value) = reply_to {
+            builder = builder.reply_to(value);
+        }
+        if let Some(value) = expiration {
+            builder = builder.expiration(value);
+        }
+        if let Some(value) = message_id {
+            builder = builder.message_id(value);
+        }
+        if let Some(value) = timestamp {
+            builder = builder.timestamp(value);
+        }
+        if let Some(value) = message_type {
+            builder = builder.message_type(value);
+        }
+        if let Some(value) = user_id {
+            builder = builder.user_id(value);
+        }
+        if let Some(value) = app_id {
+            builder = builder.app_id(value);
+        }
+        if let Some(value) = cluster_id {
+            builder = builder.cluster_id(value);
+        }
+        builder.build()
+    })
+}
+
+pub(crate) fn method_header(channel_id: u16) -> Header {
+    Header {
+        frame_type: FrameType::Method,
+        channel_id,
+        size: 0,
+    }
+}
+
+// Encodes without patching the frame size, so the decoded value compares equal
+pub(crate) fn assert_round_trip<T>(value: T) -> Result<(), TestCaseError>
+where
+    T: bincode::Encode + bincode::Decode + PartialEq + std::fmt::Debug,
+{
+    let bytes = encode_frame_static(&value).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    let decoded: T = decode_frame(&bytes).map_err(|e| TestCaseError::fail(e.to_string()))?;
+    prop_assert_eq!(decoded, value);
+    Ok(())
+}
+
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn test_strings_round_trip(short in short_string(), long in long_string()) {
+            assert_round_trip(short)?;
+            assert_round_trip(long)?;
+        }
+
+        #[test]
+        fn test_table_round_trip(table in table()) {
+            assert_round_trip(table)?;
+        }
+
+        #[test]
+        fn test_properties_round_trip(properties in properties(), delivery_mode in delivery_mode()) {
+            assert_round_trip(properties)?;
+            assert_round_trip(delivery_mode)?;
+        }
+
+        #[test]
+        fn test_bits_round_trip(one in bits::<1>(), five in bits::<5>()) {
+            assert_round_trip(one)?;
+            assert_round_trip(five)?;
+        }
+
+        #[test]
+        fn test_header_round_trip(
+            frame_type in prop_oneof![
+                Just(FrameType::Method),
+                Just(FrameType::Header),
+                Just(FrameType::Body),
+                Just(FrameType::Heartbeat),
+            ],
+            channel_id: u16,
+            size: u32,
+            exchange_type in exchange_type(),
+        ) {
+            assert_round_trip(Header { frame_type, channel_id, size })?;
+            assert_round_trip(exchange_type)?;
+        }
+
+        // Whatever the bytes, decoding fails cleanly instead of panicking
+        #[test]
+        fn test_decode_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            let _ = decode_frame::<Table>(&bytes);
+            let _ = decode_frame::<Properties>(&bytes);
+            let _ = decode_frame::<Header>(&bytes);
+            let _ = decode_frame::<ExchangeType>(&bytes);
+            let _ = decode_frame::<LongString>(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_deeply_nested_table() {
+        // Each level is a one entry table holding the next: key "", type 'F', length
+        let mut bytes = Vec::new();
+        for level in 0..10_000_u32 {
+            let length = (10_000 - level) * 6;
+            bytes.extend_from_slice(&length.to_be_bytes());
+            bytes.extend_from_slice(&[0, b'F']);
+        }
+        bytes.extend_from_slice(&0_u32.to_be_bytes());
+        assert!(decode_frame::<Table>(&bytes).is_err());
+    }
+}
@@ -1,6 +1,17 @@
-use super::{LongString, ShortString};
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug, Clone, Default)]
+use bincode::enc::write::Writer;
+use bincode::Decode;
+
+use super::long_string::long_string_length;
+use super::short_string::short_string_length;
+use super::{read_bytes, LongString, ShortString};
+
+// Nested tables and arrays are decoded recursively, so a malicious frame could
+// otherwise nest them deep enough to overflow the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Table(pub Vec<(String, Field)>);
 
 impl std::ops::Deref for Table {
@@ -12,36 +23,105 @@ impl std::ops::Deref for Table {
 }
 
 impl Table {
-    fn to_bytes(&self) -> Vec<u8> {
+    pub fn get(&self, key: &str) -> Option<&Field> {
+        self.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            Field::SS(ShortString(s)) => Some(s),
+            Field::LS(s) => s.as_str(),
+            _ => None,
+        }
+    }
+
+    pub fn get_bytes(&self, key: &str) -> Option<&[u8]> {
+        match self.get(key)? {
+            Field::SS(ShortString(s)) => Some(s.as_bytes()),
+            Field::LS(s) => Some(s.as_bytes()),
+            Field::ByteArray(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn get_i64(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            Field::I8(i) => Some(*i as i64),
+            Field::U8(i) => Some(*i as i64),
+            Field::I16(i) => Some(*i as i64),
+            Field::U16(i) => Some(*i as i64),
+            Field::I32(i) => Some(*i as i64),
+            Field::U32(i) => Some(*i as i64),
+            Field::I64(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    // Seconds since the epoch, e.g. the `time` of an `x-death` entry
+    pub fn get_timestamp(&self, key: &str) -> Option<u64> {
+        match self.get(key)? {
+            Field::Timestamp(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            Field::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn get_table(&self, key: &str) -> Option<&Table> {
+        match self.get(key)? {
+            Field::T(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    // Adds or replaces a field
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<Field>) {
+        let key = key.into();
+        self.0.retain(|(k, _)| *k != key);
+        self.0.push((key, value.into()));
+    }
+
+    pub fn builder() -> TableBuilder {
+        TableBuilder {
+            table: Table::default(),
+        }
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, bincode::error::EncodeError> {
         let mut bytes: Vec<u8> = Vec::new();
-        for (key, value) in self.clone().iter() {
-            bytes.push(key.len() as u8); // Key is a short string, push it's length as u8
+        for (key, value) in self.iter() {
+            bytes.push(short_string_length(key)?); // Key is a short string
             bytes.extend_from_slice(key.as_bytes());
+            value.write(&mut bytes)?;
+        }
+        with_length(bytes)
+    }
 
-            match value {
-                Field::SS(s) => {
-                    bytes.push(b's');
-                    bytes.push(s.len() as u8);
-                    bytes.extend_from_slice(s.as_bytes());
-                }
-                Field::LS(s) => {
-                    bytes.push(b'S');
-                    bytes.extend_from_slice(&(s.len() as u32).to_be_bytes());
-                    bytes.extend_from_slice(s.as_bytes());
-                }
-                Field::T(t) => {
-                    bytes.push(b'F');
-                    bytes.extend_from_slice(&t.to_bytes());
-                }
-                Field::Bool(b) => {
-                    bytes.push(b't');
-                    bytes.push(*b as u8);
-                }
-            }
+    // Decodes a table along with its encoded size, which the parent table needs
+    // to know how much of its own length has been parsed
+    fn decode_sized<D: bincode::de::Decoder>(
+        decoder: &mut D,
+        depth: usize,
+    ) -> Result<(Self, usize), bincode::error::DecodeError> {
+        let length = u32::decode(decoder)? as usize;
+        let mut table = vec![];
+        let mut parsed: usize = 0;
+        while parsed < length {
+            let ShortString(name) = ShortString::decode(decoder)?;
+            parsed += 1 + name.len();
+
+            let (value, size) = Field::decode_sized(decoder, depth)?;
+            parsed += size;
+
+            table.push((name, value));
         }
-        let mut length_bytes = (bytes.len() as u32).to_be_bytes().to_vec();
-        length_bytes.extend_from_slice(&bytes);
-        length_bytes
+
+        Ok((Self(table), 4 + length))
     }
 }
 
@@ -52,11 +132,8 @@ impl bincode::Encode for Table {
         &self,
         encoder: &mut E,
     ) -> Result<(), bincode::error::EncodeError> {
-        let bytes = self.to_bytes();
-        for item in bytes.iter() {
-            item.encode(encoder)?;
-        }
-        Ok(())
+        let bytes = self.to_bytes()?;
+        encoder.writer().write(&bytes)
     }
 }
 
@@ -64,80 +141,346 @@ impl bincode::Decode for Table {
     fn decode<D: bincode::de::Decoder>(
         decoder: &mut D,
     ) -> Result<Self, bincode::error::DecodeError> {
-        let length = u32::decode(decoder)? as usize;
-        let mut table = vec![];
-        let mut parsed: usize = 0;
-        while parsed < length {
-            let key_length = u8::decode(decoder)?;
-            parsed += 1;
-
-            let mut string_vec = vec![];
-            for _ in 0..key_length {
-                string_vec.push(u8::decode(decoder)?);
-            }
-            parsed += string_vec.len();
-            let name = String::from_utf8(string_vec).unwrap();
-
-            let field_type = char::decode(decoder).unwrap();
-            parsed += 1;
-
-            let val = match field_type {
-                's' => {
-                    let key_length = u8::decode(decoder)?;
-                    parsed += 1;
-                    let mut string_vec = vec![];
-                    for _ in 0..key_length {
-                        string_vec.push(u8::decode(decoder)?);
-                    }
-                    parsed += string_vec.len();
-                    let name = String::from_utf8(string_vec).unwrap();
-                    Field::SS(ShortString(name))
-                }
-                'S' => {
-                    let key_length = u32::decode(decoder)?;
-                    parsed += 4;
-                    let mut string_vec = vec![];
-                    for _ in 0..key_length {
-                        string_vec.push(u8::decode(decoder)?);
-                    }
-                    parsed += string_vec.len();
-                    let name = String::from_utf8(string_vec).unwrap();
-                    Field::LS(LongString(name))
-                }
-                'F' => {
-                    // This is pretty hacky, but once decoding an inner table, we have to convert back to bytes to get the length.
-                    let x = Table::decode(decoder)?;
-                    parsed += x.to_bytes().len();
-                    Field::T(x)
-                }
-                't' => {
-                    let x = bool::decode(decoder)?;
-                    parsed += 1;
-                    Field::Bool(x)
-                }
+        let (table, _size) = Table::decode_sized(decoder, 0)?;
+        Ok(table)
+    }
+}
 
-                _ => {
-                    println!("{field_type:?} not supported yet");
-                    todo!();
-                }
-            };
+// Prefixes encoded tables and arrays with their length
+fn with_length(bytes: Vec<u8>) -> Result<Vec<u8>, bincode::error::EncodeError> {
+    let mut length_bytes = long_string_length(&bytes)?.to_be_bytes().to_vec();
+    length_bytes.extend_from_slice(&bytes);
+    Ok(length_bytes)
+}
 
-            table.push((name, val));
+pub struct TableBuilder {
+    table: Table,
+}
+
+impl TableBuilder {
+    pub fn insert(mut self, key: impl Into<String>, value: impl Into<Field>) -> Self {
+        self.table.insert(key, value);
+        self
+    }
+
+    pub fn build(self) -> Table {
+        self.table
+    }
+}
+
+// Builds a Table from `key => value` pairs, where a value is anything that converts
+// into a Field, e.g. `table! { "x-queue-type" => "quorum", "x-max-length" => 1000 }`.
+// u64 and usize values may not fit and go through `Field::try_from` first.
+#[macro_export]
+macro_rules! table {
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::Table::builder()$(.insert($key, $value))*.build()
+    };
+}
+
+impl<K: Into<String>, V: Into<Field>> FromIterator<(K, V)> for Table {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut table = Table::default();
+        for (key, value) in iter {
+            table.insert(key, value);
         }
+        table
+    }
+}
+
+impl<K: Into<String>, V: Into<Field>> From<HashMap<K, V>> for Table {
+    fn from(value: HashMap<K, V>) -> Self {
+        value.into_iter().collect()
+    }
+}
 
-        Ok(Self(table))
+impl<K: Into<String>, V: Into<Field>> From<BTreeMap<K, V>> for Table {
+    fn from(value: BTreeMap<K, V>) -> Self {
+        value.into_iter().collect()
     }
 }
 
 //////////////////////////////////////////////////
 // Here we need to add all fields under a common enum simply for the table.
 // we do not need to implement enc/dec directly here
-#[derive(Debug, Clone)]
+//
+// Type octets follow the 0-9-1 spec, apart from 'l' which is a signed 64 bit integer
+// as in RabbitMQ. 'L' is read the same way and written back as 'l'.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Field {
     SS(ShortString),
     LS(LongString),
     T(Table),
     Bool(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    // `value` divided by 10 to the power of `scale`
+    Decimal { scale: u8, value: u32 },
+    // Seconds since the epoch
+    Timestamp(u64),
+    A(Vec<Field>),
+    ByteArray(Vec<u8>),
+    Void,
+}
+
+impl Field {
+    // Writes the field type octet followed by the value
+    fn write(&self, bytes: &mut Vec<u8>) -> Result<(), bincode::error::EncodeError> {
+        match self {
+            Field::SS(s) => {
+                bytes.push(b's');
+                bytes.push(short_string_length(s)?);
+                bytes.extend_from_slice(s.as_bytes());
+            }
+            Field::LS(s) => {
+                bytes.push(b'S');
+                bytes.extend_from_slice(&long_string_length(s)?.to_be_bytes());
+                bytes.extend_from_slice(s.as_bytes());
+            }
+            Field::T(t) => {
+                bytes.push(b'F');
+                bytes.extend_from_slice(&t.to_bytes()?);
+            }
+            Field::Bool(b) => {
+                bytes.push(b't');
+                bytes.push(*b as u8);
+            }
+            Field::I8(i) => {
+                bytes.push(b'b');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::U8(i) => {
+                bytes.push(b'B');
+                bytes.push(*i);
+            }
+            Field::I16(i) => {
+                bytes.push(b'U');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::U16(i) => {
+                bytes.push(b'u');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::I32(i) => {
+                bytes.push(b'I');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::U32(i) => {
+                bytes.push(b'i');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::I64(i) => {
+                bytes.push(b'l');
+                bytes.extend_from_slice(&i.to_be_bytes());
+            }
+            Field::F32(f) => {
+                bytes.push(b'f');
+                bytes.extend_from_slice(&f.to_be_bytes());
+            }
+            Field::F64(f) => {
+                bytes.push(b'd');
+                bytes.extend_from_slice(&f.to_be_bytes());
+            }
+            Field::Decimal { scale, value } => {
+                bytes.push(b'D');
+                bytes.push(*scale);
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            Field::Timestamp(t) => {
+                bytes.push(b'T');
+                bytes.extend_from_slice(&t.to_be_bytes());
+            }
+            Field::A(fields) => {
+                bytes.push(b'A');
+                let mut array = Vec::new();
+                for field in fields {
+                    field.write(&mut array)?;
+                }
+                bytes.extend_from_slice(&with_length(array)?);
+            }
+            Field::ByteArray(array) => {
+                bytes.push(b'x');
+                bytes.extend_from_slice(&long_string_length(array)?.to_be_bytes());
+                bytes.extend_from_slice(array);
+            }
+            Field::Void => bytes.push(b'V'),
+        }
+        Ok(())
+    }
+
+    // Decodes the field type octet and value, along with their encoded size
+    fn decode_sized<D: bincode::de::Decoder>(
+        decoder: &mut D,
+        depth: usize,
+    ) -> Result<(Self, usize), bincode::error::DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(bincode::error::DecodeError::OtherString(format!(
+                "Tables and arrays nested deeper than {MAX_DEPTH} levels"
+            )));
+        }
+        let field_type = u8::decode(decoder)?;
+        let (field, size) = match field_type {
+            b's' => {
+                let value = ShortString::decode(decoder)?;
+                let size = 1 + value.len();
+                (Field::SS(value), size)
+            }
+            b'S' => {
+                let value = LongString::decode(decoder)?;
+                let size = 4 + value.len();
+                (Field::LS(value), size)
+            }
+            b'F' => {
+                let (value, size) = Table::decode_sized(decoder, depth + 1)?;
+                (Field::T(value), size)
+            }
+            b't' => (Field::Bool(bool::decode(decoder)?), 1),
+            b'b' => (Field::I8(i8::decode(decoder)?), 1),
+            b'B' => (Field::U8(u8::decode(decoder)?), 1),
+            b'U' => (Field::I16(i16::decode(decoder)?), 2),
+            b'u' => (Field::U16(u16::decode(decoder)?), 2),
+            b'I' => (Field::I32(i32::decode(decoder)?), 4),
+            b'i' => (Field::U32(u32::decode(decoder)?), 4),
+            b'l' | b'L' => (Field::I64(i64::decode(decoder)?), 8),
+            b'f' => (Field::F32(f32::decode(decoder)?), 4),
+            b'd' => (Field::F64(f64::decode(decoder)?), 8),
+            b'D' => {
+                let scale = u8::decode(decoder)?;
+                let value = u32::decode(decoder)?;
+                (Field::Decimal { scale, value }, 5)
+            }
+            b'T' => (Field::Timestamp(u64::decode(decoder)?), 8),
+            b'x' => {
+                let length = u32::decode(decoder)? as usize;
+                (Field::ByteArray(read_bytes(decoder, length)?), 4 + length)
+            }
+            b'V' => (Field::Void, 0),
+            b'A' => {
+                let length = u32::decode(decoder)? as usize;
+                let mut fields = vec![];
+                let mut parsed: usize = 0;
+                while parsed < length {
+                    let (field, size) = Field::decode_sized(decoder, depth + 1)?;
+                    parsed += size;
+                    fields.push(field);
+                }
+                (Field::A(fields), 4 + length)
+            }
+            _ => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Field type {:?} not supported",
+                    field_type as char
+                )))
+            }
+        };
+        Ok((field, 1 + size))
+    }
+}
+
+// Strings become long strings, RabbitMQ does not accept short strings in tables
+impl From<&str> for Field {
+    fn from(value: &str) -> Self {
+        Field::LS(value.into())
+    }
+}
+impl From<String> for Field {
+    fn from(value: String) -> Self {
+        Field::LS(value.into())
+    }
+}
+impl From<ShortString> for Field {
+    fn from(value: ShortString) -> Self {
+        Field::SS(value)
+    }
+}
+impl From<LongString> for Field {
+    fn from(value: LongString) -> Self {
+        Field::LS(value)
+    }
+}
+impl From<bool> for Field {
+    fn from(value: bool) -> Self {
+        Field::Bool(value)
+    }
+}
+impl From<i8> for Field {
+    fn from(value: i8) -> Self {
+        Field::I32(value.into())
+    }
+}
+impl From<u8> for Field {
+    fn from(value: u8) -> Self {
+        Field::I32(value.into())
+    }
+}
+impl From<i16> for Field {
+    fn from(value: i16) -> Self {
+        Field::I32(value.into())
+    }
+}
+impl From<u16> for Field {
+    fn from(value: u16) -> Self {
+        Field::I32(value.into())
+    }
+}
+impl From<i32> for Field {
+    fn from(value: i32) -> Self {
+        Field::I32(value)
+    }
+}
+impl From<u32> for Field {
+    fn from(value: u32) -> Self {
+        Field::I64(value.into())
+    }
+}
+impl From<i64> for Field {
+    fn from(value: i64) -> Self {
+        Field::I64(value)
+    }
+}
+// AMQP has no unsigned 64 bit field, anything past i64::MAX is an error
+impl TryFrom<u64> for Field {
+    type Error = crate::types::Error;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        i64::try_from(value)
+            .map(Field::I64)
+            .map_err(|_| crate::types::Error::Table(format!("{value} does not fit in an i64")))
+    }
+}
+impl TryFrom<usize> for Field {
+    type Error = crate::types::Error;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Field::try_from(value as u64)
+    }
+}
+impl From<f32> for Field {
+    fn from(value: f32) -> Self {
+        Field::F32(value)
+    }
+}
+impl From<f64> for Field {
+    fn from(value: f64) -> Self {
+        Field::F64(value)
+    }
+}
+impl From<Table> for Field {
+    fn from(value: Table) -> Self {
+        Field::T(value)
+    }
+}
+impl<T: Into<Field>> From<Vec<T>> for Field {
+    fn from(value: Vec<T>) -> Self {
+        Field::A(value.into_iter().map(Into::into).collect())
+    }
 }
 
 /////////////////////////////////////////////
@@ -148,7 +491,7 @@ mod tests {
     use super::*;
     #[test]
     fn test_table() {
-        #[derive(Debug, bincode::Encode, bincode::Decode)]
+        #[derive(Debug, PartialEq, bincode::Encode, bincode::Decode)]
         struct TableTest {
             inner: Table,
         }
@@ -156,10 +499,163 @@ mod tests {
         let original = TableTest {
             inner: Table(vec![("abc".into(), Field::T(sub_table))]),
         };
+        let config = bincode::config::standard()
+            .with_big_endian()
+            .with_fixed_int_encoding();
+        let encoded = bincode::encode_to_vec(&original, config).unwrap();
+        let (decoded, _): (TableTest, usize) =
+            bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn test_table_lookups() {
+        let sub_table = Table(vec![("def".into(), Field::Bool(true))]);
+        let table = Table(vec![
+            ("short".into(), Field::SS("abc".into())),
+            ("long".into(), Field::LS("def".into())),
+            ("int".into(), Field::I32(7)),
+            ("table".into(), Field::T(sub_table)),
+        ]);
+        assert_eq!(table.get_str("short"), Some("abc"));
+        assert_eq!(table.get_str("long"), Some("def"));
+        assert_eq!(table.get_i64("int"), Some(7));
+        assert_eq!(table.get_str("int"), None);
+        assert_eq!(
+            table.get_table("table").unwrap().get_bool("def"),
+            Some(true)
+        );
+        assert!(table.get("missing").is_none());
+    }
+
+    #[test]
+    fn test_table_integers() {
+        let original = Table(vec![
+            ("x-priority".into(), Field::I32(-5)),
+            ("x-stream-offset".into(), Field::I64(1 << 40)),
+        ]);
         let config = bincode::config::standard()
             .with_big_endian()
             .with_fixed_int_encoding();
         let encoded = bincode::encode_to_vec(original, config).unwrap();
-        let (_, _): (TableTest, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        let (decoded, _): (Table, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert!(matches!(decoded[0], (ref k, Field::I32(-5)) if k == "x-priority"));
+        assert!(
+            matches!(decoded[1], (ref k, Field::I64(v)) if k == "x-stream-offset" && v == 1 << 40)
+        );
+
+        let table = crate::table! { "x-max-length-bytes" => Field::try_from(10_u64).unwrap() };
+        assert_eq!(table.get_i64("x-max-length-bytes"), Some(10));
+        assert_eq!(Field::try_from(1_usize).unwrap(), Field::I64(1));
+        assert!(Field::try_from(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_table_utf8() {
+        let original = Table(vec![
+            ("schlüssel".into(), Field::SS("wert ✓".into())),
+            ("キー".into(), Field::LS("値".into())),
+        ]);
+        let config = bincode::config::standard()
+            .with_big_endian()
+            .with_fixed_int_encoding();
+        let encoded = bincode::encode_to_vec(&original, config).unwrap();
+        let (decoded, _): (Table, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded.get_str("schlüssel"), Some("wert ✓"));
+        assert_eq!(decoded.get_str("キー"), Some("値"));
+
+        let too_long = Table(vec![("k".repeat(256), Field::Bool(true))]);
+        assert!(bincode::encode_to_vec(too_long, config).is_err());
+    }
+
+    #[test]
+    fn test_table_builder() {
+        let table = crate::table! {
+            "x-queue-type" => "quorum",
+            "x-max-length" => 1000,
+            "x-stream-offset" => 1_i64 << 40,
+            "x-tags" => vec!["a", "b"],
+            "nested" => crate::table! { "flag" => true },
+        };
+        assert_eq!(table.get_str("x-queue-type"), Some("quorum"));
+        assert_eq!(table.get_i64("x-max-length"), Some(1000));
+
+        // Inserting an existing key replaces it
+        let table = Table::builder()
+            .insert("key", 1)
+            .insert("key", "value")
+            .build();
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get_str("key"), Some("value"));
+
+        let map = BTreeMap::from([("a", 1_u32), ("b", 2)]);
+        let table = Table::from(map);
+        assert_eq!(table.get_i64("b"), Some(2));
+    }
+
+    #[test]
+    fn test_table_array_round_trip() {
+        let original = crate::table! {
+            "x-tags" => vec![Field::from("a"), Field::from(7), Field::from(vec![true])],
+            "nested" => crate::table! { "flag" => true },
+        };
+        let config = bincode::config::standard()
+            .with_big_endian()
+            .with_fixed_int_encoding();
+        let encoded = bincode::encode_to_vec(&original, config).unwrap();
+        let (decoded, size): (Table, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(size, encoded.len());
+        let Some(Field::A(tags)) = decoded.get("x-tags") else {
+            panic!("Expected an array");
+        };
+        assert!(matches!(tags[0], Field::LS(ref s) if s.as_str() == Some("a")));
+        assert!(matches!(tags[1], Field::I32(7)));
+        assert!(matches!(tags[2], Field::A(ref inner) if matches!(inner[..], [Field::Bool(true)])));
+        assert_eq!(
+            decoded.get_table("nested").unwrap().get_bool("flag"),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_table_field_types() {
+        // An x-death entry as RabbitMQ writes it, with a timestamp, followed by a
+        // byte array and a void field
+        let bytes: Vec<u8> = [
+            &[0x00, 0x00, 0x00, 0x29][..],
+            b"\x05count",
+            b"l",
+            &1_i64.to_be_bytes(),
+            b"\x04time",
+            b"T",
+            &1700000000_u64.to_be_bytes(),
+            &[0x01, b'x', b'x', 0x00, 0x00, 0x00, 0x02, 0xff, 0x00],
+            &[0x01, b'v', b'V'],
+        ]
+        .concat();
+        let table: Table = crate::encde::decode_frame(&bytes).unwrap();
+        assert_eq!(table.get_i64("count"), Some(1));
+        assert_eq!(table.get_timestamp("time"), Some(1700000000));
+        assert_eq!(table.get_bytes("x"), Some(&[0xff, 0x00][..]));
+        assert_eq!(table.get("v"), Some(&Field::Void));
+        assert_eq!(crate::encde::encode_frame_static(&table).unwrap(), bytes);
+
+        let original = crate::table! {
+            "b" => Field::I8(-1),
+            "B" => Field::U8(255),
+            "U" => Field::I16(-2),
+            "u" => Field::U16(65535),
+            "i" => Field::U32(u32::MAX),
+            "D" => Field::Decimal { scale: 2, value: 12345 },
+        };
+        let encoded = crate::encde::encode_frame_static(&original).unwrap();
+        let decoded: Table = crate::encde::decode_frame(&encoded).unwrap();
+        assert_eq!(decoded, original);
+        assert_eq!(decoded.get_i64("i"), Some(u32::MAX as i64));
+
+        // 'L' is read like 'l'
+        let bytes = [&[0, 0, 0, 11, 1, b'L', b'L'][..], &(-3_i64).to_be_bytes()].concat();
+        let table: Table = crate::encde::decode_frame(&bytes).unwrap();
+        assert_eq!(table.get_i64("L"), Some(-3));
     }
 }
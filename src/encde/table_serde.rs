@@ -0,0 +1,477 @@
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+
+use super::{Field, ShortString, Table};
+use crate::types::Error;
+
+impl Table {
+    // Serializes a struct or map into a table, e.g. to use as `Properties.headers`.
+    // Integers become I32 or I64 depending on their range, strings become long
+    // strings, sequences become arrays and `None` fields are left out.
+    pub fn from_serialize<T: Serialize + ?Sized>(value: &T) -> crate::Result<Self> {
+        match value.serialize(FieldSerializer)? {
+            Some(Field::T(table)) => Ok(table),
+            _ => Err(table_error("only structs and maps can be serialized into a table").into()),
+        }
+    }
+
+    // Deserializes the table into a struct or map, fields missing from the table
+    // deserialize as `None`
+    pub fn deserialize<T: DeserializeOwned>(&self) -> crate::Result<T> {
+        Ok(T::deserialize(Field::T(self.clone()))?)
+    }
+}
+
+fn table_error(message: impl std::fmt::Display) -> Error {
+    Error::Table(message.to_string())
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        table_error(msg)
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        table_error(msg)
+    }
+}
+
+// Serializes a value into a field, `None` standing for a value to leave out
+struct FieldSerializer;
+
+impl ser::Serializer for FieldSerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = ArraySerializer;
+    type SerializeTupleStruct = ArraySerializer;
+    type SerializeTupleVariant = Impossible<Option<Field>, Error>;
+    type SerializeMap = TableSerializer;
+    type SerializeStruct = TableSerializer;
+    type SerializeStructVariant = Impossible<Option<Field>, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::try_from(v)?))
+    }
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Error> {
+        Ok(Some(v.to_string().into()))
+    }
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Error> {
+        Ok(Some(v.into()))
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::LS(v.into())))
+    }
+    fn serialize_none(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Error> {
+        Ok(None)
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Error> {
+        Ok(Some(variant.into()))
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        value.serialize(self)
+    }
+    // Written as a single entry table keyed by the variant name
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Error> {
+        let mut table = Table::default();
+        if let Some(field) = value.serialize(FieldSerializer)? {
+            table.insert(variant, field);
+        }
+        Ok(Some(Field::T(table)))
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(ArraySerializer {
+            fields: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(table_error(format!(
+            "tuple variant {name}::{variant} is not supported"
+        )))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Ok(TableSerializer::default())
+    }
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(TableSerializer::default())
+    }
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(table_error(format!(
+            "struct variant {name}::{variant} is not supported"
+        )))
+    }
+}
+
+struct ArraySerializer {
+    fields: Vec<Field>,
+}
+
+impl ArraySerializer {
+    // Arrays have no way to leave an element out
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let field = value
+            .serialize(FieldSerializer)?
+            .ok_or_else(|| table_error("arrays can not contain None or ()"))?;
+        self.fields.push(field);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ArraySerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::A(self.fields)))
+    }
+}
+
+impl ser::SerializeTuple for ArraySerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::A(self.fields)))
+    }
+}
+
+impl ser::SerializeTupleStruct for ArraySerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::A(self.fields)))
+    }
+}
+
+#[derive(Default)]
+struct TableSerializer {
+    table: Table,
+    key: Option<String>,
+}
+
+impl TableSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        if let Some(field) = value.serialize(FieldSerializer)? {
+            self.table.insert(key, field);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for TableSerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = match key.serialize(FieldSerializer)? {
+            Some(Field::LS(key)) => key.as_str().map(str::to_owned),
+            _ => None,
+        };
+        self.key = Some(key.ok_or_else(|| table_error("table keys must be strings"))?);
+        Ok(())
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| table_error("value serialized before its key"))?;
+        self.insert(key, value)
+    }
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::T(self.table)))
+    }
+}
+
+impl ser::SerializeStruct for TableSerializer {
+    type Ok = Option<Field>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.into(), value)
+    }
+    fn end(self) -> Result<Self::Ok, Error> {
+        Ok(Some(Field::T(self.table)))
+    }
+}
+
+// Fields are self describing, so everything goes through deserialize_any apart from
+// options, newtypes and enums which serde can not tell apart on its own
+impl<'de> de::Deserializer<'de> for Field {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Field::SS(ShortString(s)) => visitor.visit_string(s),
+            // Long strings are bytes, only valid UTF-8 is offered as a string
+            Field::LS(s) => match String::from_utf8(s.0) {
+                Ok(s) => visitor.visit_string(s),
+                Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+            },
+            Field::T(table) => visitor.visit_map(MapDeserializer::new(table.0.into_iter())),
+            Field::Bool(b) => visitor.visit_bool(b),
+            Field::I8(i) => visitor.visit_i8(i),
+            Field::U8(i) => visitor.visit_u8(i),
+            Field::I16(i) => visitor.visit_i16(i),
+            Field::U16(i) => visitor.visit_u16(i),
+            Field::I32(i) => visitor.visit_i32(i),
+            Field::U32(i) => visitor.visit_u32(i),
+            Field::I64(i) => visitor.visit_i64(i),
+            Field::F32(f) => visitor.visit_f32(f),
+            Field::F64(f) => visitor.visit_f64(f),
+            Field::Decimal { scale, value } => {
+                visitor.visit_f64(value as f64 / 10_f64.powi(scale as i32))
+            }
+            Field::Timestamp(t) => visitor.visit_u64(t),
+            Field::A(fields) => visitor.visit_seq(SeqDeserializer::new(fields.into_iter())),
+            Field::ByteArray(bytes) => visitor.visit_byte_buf(bytes),
+            Field::Void => visitor.visit_unit(),
+        }
+    }
+
+    // None is never written, so apart from a void field a field that is present is
+    // always Some
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Field::Void => visitor.visit_none(),
+            field => visitor.visit_some(field),
+        }
+    }
+
+    // Byte arrays are offered as bytes by deserialize_any, and as a sequence of u8 to
+    // types such as Vec<u8> that ask for one
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Field::ByteArray(bytes) => visitor.visit_seq(SeqDeserializer::new(bytes.into_iter())),
+            field => field.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Field::SS(ShortString(variant)) => visitor.visit_enum(variant.into_deserializer()),
+            Field::LS(variant) => match variant.as_str() {
+                Some(variant) => visitor.visit_enum(variant.into_deserializer()),
+                None => Err(table_error(format!("invalid UTF-8 variant for {name}"))),
+            },
+            Field::T(table) => visitor.visit_enum(MapAccessDeserializer::new(
+                MapDeserializer::new(table.0.into_iter()),
+            )),
+            _ => Err(table_error(format!("expected a variant of {name}"))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Field {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Priority {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Headers {
+        tenant: String,
+        attempt: u16,
+        offset: u64,
+        score: f64,
+        retry: bool,
+        trace_id: Option<String>,
+        tags: Vec<String>,
+        priority: Priority,
+        extra: BTreeMap<String, i32>,
+    }
+
+    #[test]
+    fn test_struct_round_trip() {
+        let headers = Headers {
+            tenant: "acme".into(),
+            attempt: 3,
+            offset: 1 << 40,
+            score: 0.5,
+            retry: true,
+            trace_id: None,
+            tags: vec!["a".into(), "b".into()],
+            priority: Priority::High,
+            extra: BTreeMap::from([("x".into(), -1)]),
+        };
+        let table = Table::from_serialize(&headers).unwrap();
+        assert_eq!(table.get_str("tenant"), Some("acme"));
+        assert!(matches!(table.get("attempt"), Some(Field::I32(3))));
+        assert!(matches!(table.get("offset"), Some(Field::I64(_))));
+        assert_eq!(table.get_str("priority"), Some("High"));
+        assert!(table.get("trace_id").is_none());
+
+        // Through the wire format and back
+        let config = bincode::config::standard()
+            .with_big_endian()
+            .with_fixed_int_encoding();
+        let encoded = bincode::encode_to_vec(&table, config).unwrap();
+        let (decoded, _): (Table, usize) = bincode::decode_from_slice(&encoded, config).unwrap();
+        assert_eq!(decoded.deserialize::<Headers>().unwrap(), headers);
+    }
+
+    #[test]
+    fn test_unsupported_values() {
+        assert!(Table::from_serialize(&1_u32).is_err());
+        assert!(Table::from_serialize(&BTreeMap::from([("big", u64::MAX)])).is_err());
+        assert!(Table::from_serialize(&BTreeMap::from([("a", vec![None::<u8>])])).is_err());
+
+        let table = crate::table! { "attempt" => "three" };
+        assert!(table.deserialize::<BTreeMap<String, u16>>().is_err());
+    }
+
+    #[test]
+    fn test_broker_field_types() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Death {
+            count: i64,
+            time: u64,
+            reason: Option<String>,
+            trace: Vec<u8>,
+        }
+        let table = crate::table! {
+            "count" => 1_i64,
+            "time" => Field::Timestamp(1700000000),
+            "reason" => Field::Void,
+            "trace" => Field::ByteArray(vec![0xff, 0x00]),
+        };
+        let death = Death {
+            count: 1,
+            time: 1700000000,
+            reason: None,
+            trace: vec![0xff, 0x00],
+        };
+        assert_eq!(table.deserialize::<Death>().unwrap(), death);
+    }
+}
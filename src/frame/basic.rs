@@ -1,18 +1,18 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 struct BasicFrameInfo {
     header: Header,
     class_id: ClassID,
     method_id: BasicMethodID,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct QualityOfService {
     frame_info: BasicFrameInfo,
     prefetch_size: u32,
     prefetch_count: u16,
-    global: Bits,
+    global: Bits<1>,
 }
 
 impl QualityOfService {
@@ -36,25 +36,39 @@ impl QualityOfService {
             global: (global,).into(),
         }
     }
+
+    pub fn global(&self) -> bool {
+        self.global[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct QualityOfServiceOk {
     frame_info: BasicFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Consume {
     frame_info: BasicFrameInfo,
     reserved_1: u16,
     queue_name: ShortString,
     consumer_tag: ShortString,
-    bits: Bits,
+    bits: Bits<4>, // no_local, no_ack, exclusive, no_wait
     arguments: Table,
 }
 
 impl Consume {
-    pub fn new(channel_id: u16, queue_name: &str) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        channel_id: u16,
+        queue_name: &str,
+        consumer_tag: &str,
+        no_local: bool,
+        no_ack: bool,
+        exclusive: bool,
+        no_wait: bool,
+        arguments: Table,
+    ) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
             channel_id,
@@ -71,23 +85,36 @@ impl Consume {
             frame_info,
             reserved_1: RESERVED16,
             queue_name: queue_name.into(),
-            consumer_tag: ShortString("abc".into()),
-            bits: Bits::default(),
-            arguments: Table::default(),
+            consumer_tag: consumer_tag.into(),
+            bits: (no_local, no_ack, exclusive, no_wait).into(),
+            arguments,
         }
     }
+
+    pub fn no_local(&self) -> bool {
+        self.bits[0]
+    }
+    pub fn no_ack(&self) -> bool {
+        self.bits[1]
+    }
+    pub fn exclusive(&self) -> bool {
+        self.bits[2]
+    }
+    pub fn no_wait(&self) -> bool {
+        self.bits[3]
+    }
 }
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct ConsumeOk {
     frame_info: BasicFrameInfo,
-    consumer_tag: ShortString,
+    pub consumer_tag: ShortString,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Cancel {
     frame_info: BasicFrameInfo,
-    consumer_tag: ShortString,
-    no_wait: Bits,
+    pub consumer_tag: ShortString,
+    no_wait: Bits<1>,
 }
 
 impl Cancel {
@@ -110,30 +137,55 @@ impl Cancel {
             no_wait: no_wait.into(),
         }
     }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct CancelOk {
     frame_info: BasicFrameInfo,
-    consumer_tag: ShortString,
+    pub consumer_tag: ShortString,
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+impl CancelOk {
+    pub fn new(channel_id: u16, consumer_tag: &str) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Basic;
+        let method_id = BasicMethodID::CancelOk;
+        let frame_info = BasicFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self {
+            frame_info,
+            consumer_tag: consumer_tag.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Return {
     frame_info: BasicFrameInfo,
-    reply_code: u16,
-    reply_text: ShortString,
-    exchange_name: ShortString,
-    routing_key: ShortString,
+    pub reply_code: u16,
+    pub reply_text: ShortString,
+    pub exchange_name: ShortString,
+    pub routing_key: ShortString,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Publish {
     frame_info: BasicFrameInfo,
     reserved_1: u16,
     exchange_name: ShortString,
     routing_key: ShortString,
-    bits: Bits, // mandatory, immediate
+    bits: Bits<2>, // mandatory, immediate
 }
 
 impl Publish {
@@ -164,24 +216,37 @@ impl Publish {
             bits: (mandatory, immediate).into(),
         }
     }
+
+    pub fn mandatory(&self) -> bool {
+        self.bits[0]
+    }
+    pub fn immediate(&self) -> bool {
+        self.bits[1]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Deliver {
     frame_info: BasicFrameInfo,
-    consumer_tag: ShortString,
+    pub consumer_tag: ShortString,
     pub delivery_tag: u64,
-    redelivered: bool,
-    exchange: ShortString,
-    routing: ShortString,
+    redelivered: Bits<1>,
+    pub exchange: ShortString,
+    pub routing_key: ShortString,
+}
+
+impl Deliver {
+    pub fn redelivered(&self) -> bool {
+        self.redelivered[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Get {
     frame_info: BasicFrameInfo,
     reserved_1: u16,
     queue_name: ShortString,
-    no_ack: Bits,
+    no_ack: Bits<1>,
 }
 
 impl Get {
@@ -205,41 +270,51 @@ impl Get {
             no_ack: no_ack.into(),
         }
     }
+
+    pub fn no_ack(&self) -> bool {
+        self.no_ack[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct GetOk {
     frame_info: BasicFrameInfo,
-    redelivered: Bits,
-    delivery_tag: u64,
-    exchange_name: ShortString,
-    routing_key: ShortString,
-    message_count: u32,
+    pub delivery_tag: u64,
+    redelivered: Bits<1>,
+    pub exchange_name: ShortString,
+    pub routing_key: ShortString,
+    pub message_count: u32,
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+impl GetOk {
+    pub fn redelivered(&self) -> bool {
+        self.redelivered[0]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct GetEmpty {
     frame_info: BasicFrameInfo,
-    reserved_1: u16,
+    reserved_1: ShortString,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Ack {
     frame_info: BasicFrameInfo,
     delivery_tag: u64,
-    multiple: u8,
+    multiple: Bits<1>,
 }
 
 impl Ack {
-    pub fn new(delivery_tag: u64) -> Self {
+    pub fn new(channel_id: u16, delivery_tag: u64) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Basic;
         let method_id = BasicMethodID::Ack;
-        let multiple = 0;
+        let multiple = false.into();
         let frame_info = BasicFrameInfo {
             header,
             class_id,
@@ -252,13 +327,17 @@ impl Ack {
             multiple,
         }
     }
+
+    pub fn multiple(&self) -> bool {
+        self.multiple[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Reject {
     frame_info: BasicFrameInfo,
     delivery_tag: u64,
-    requeue: Bits,
+    requeue: Bits<1>,
 }
 
 impl Reject {
@@ -281,12 +360,16 @@ impl Reject {
             requeue: requeue.into(),
         }
     }
+
+    pub fn requeue(&self) -> bool {
+        self.requeue[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Recover {
     frame_info: BasicFrameInfo,
-    requeue: Bits,
+    requeue: Bits<1>,
 }
 
 impl Recover {
@@ -308,9 +391,138 @@ impl Recover {
             requeue: requeue.into(),
         }
     }
+
+    pub fn requeue(&self) -> bool {
+        self.requeue[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct RecoverOk {
     frame_info: BasicFrameInfo,
 }
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Nack {
+    frame_info: BasicFrameInfo,
+    delivery_tag: u64,
+    multiple_requeue: Bits<2>,
+}
+
+impl Nack {
+    pub fn new(channel_id: u16, delivery_tag: u64, multiple: bool, requeue: bool) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Basic;
+        let method_id = BasicMethodID::Nack;
+        let frame_info = BasicFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self {
+            frame_info,
+            delivery_tag,
+            multiple_requeue: (multiple, requeue).into(),
+        }
+    }
+
+    pub fn multiple(&self) -> bool {
+        self.multiple_requeue[0]
+    }
+    pub fn requeue(&self) -> bool {
+        self.multiple_requeue[1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    fn frame_info(channel_id: u16, method_id: BasicMethodID) -> BasicFrameInfo {
+        BasicFrameInfo {
+            header: method_header(channel_id),
+            class_id: ClassID::Basic,
+            method_id,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_client_methods_round_trip(
+            channel_id: u16,
+            queue in string(),
+            tag in string(),
+            delivery_tag: u64,
+            prefetch_size: u32,
+            prefetch_count: u16,
+            flags: [bool; 4],
+            arguments in table(),
+        ) {
+            let [a, b, c, d] = flags;
+            assert_round_trip(QualityOfService::new(channel_id, prefetch_size, prefetch_count, a))?;
+            assert_round_trip(Consume::new(channel_id, &queue, &tag, a, b, c, d, arguments))?;
+            assert_round_trip(Cancel::new(channel_id, &tag, a))?;
+            assert_round_trip(CancelOk::new(channel_id, &tag))?;
+            assert_round_trip(Publish::new(channel_id, &queue, &tag, a, b))?;
+            assert_round_trip(Get::new(channel_id, &queue, a))?;
+            assert_round_trip(Ack::new(channel_id, delivery_tag))?;
+            assert_round_trip(Reject::new(channel_id, delivery_tag, a))?;
+            assert_round_trip(Recover::new(channel_id, a))?;
+            assert_round_trip(Nack::new(channel_id, delivery_tag, a, b))?;
+        }
+
+        #[test]
+        fn test_server_methods_round_trip(
+            channel_id: u16,
+            tag in short_string(),
+            exchange in short_string(),
+            routing_key in short_string(),
+            delivery_tag: u64,
+            reply_code: u16,
+            message_count: u32,
+            redelivered in bits::<1>(),
+        ) {
+            let info = |method_id| frame_info(channel_id, method_id);
+            assert_round_trip(QualityOfServiceOk { frame_info: info(BasicMethodID::QualityOfServiceOk) })?;
+            assert_round_trip(ConsumeOk {
+                frame_info: info(BasicMethodID::ConsumeOk),
+                consumer_tag: tag.clone(),
+            })?;
+            assert_round_trip(Return {
+                frame_info: info(BasicMethodID::Return),
+                reply_code,
+                reply_text: tag.clone(),
+                exchange_name: exchange.clone(),
+                routing_key: routing_key.clone(),
+            })?;
+            assert_round_trip(Deliver {
+                frame_info: info(BasicMethodID::Deliver),
+                consumer_tag: tag.clone(),
+                delivery_tag,
+                redelivered,
+                exchange: exchange.clone(),
+                routing_key: routing_key.clone(),
+            })?;
+            assert_round_trip(GetOk {
+                frame_info: info(BasicMethodID::GetOk),
+                delivery_tag,
+                redelivered,
+                exchange_name: exchange,
+                routing_key,
+                message_count,
+            })?;
+            assert_round_trip(GetEmpty {
+                frame_info: info(BasicMethodID::GetEmpty),
+                reserved_1: tag,
+            })?;
+            assert_round_trip(RecoverOk { frame_info: info(BasicMethodID::RecoverOk) })?;
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use bytes::Bytes;
+
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Body {
     pub header: Header,
     pub content: RawBytes,
@@ -15,4 +17,31 @@ impl Body {
         };
         Self { header, content }
     }
+
+    // The body of a received frame, as a slice of the frame rather than a copy
+    pub fn payload(frame: &Bytes) -> Result<Bytes, bincode::error::DecodeError> {
+        let header: Header = decode_frame(frame)?;
+        if header.frame_type != FrameType::Body {
+            return Err(bincode::error::DecodeError::OtherString(format!(
+                "Expected a body frame, got {:?}",
+                header.frame_type
+            )));
+        }
+        if frame.len() <= HEADER_SIZE {
+            return Err(bincode::error::DecodeError::UnexpectedEnd {
+                additional: HEADER_SIZE + 1 - frame.len(),
+            });
+        }
+        Ok(frame.slice(HEADER_SIZE..frame.len() - 1))
+    }
+
+    // A body received in a single frame is kept as is, only larger ones are copied
+    // together
+    pub fn join(mut payloads: Vec<Bytes>) -> Bytes {
+        match payloads.len() {
+            0 => Bytes::new(),
+            1 => payloads.remove(0),
+            _ => payloads.concat().into(),
+        }
+    }
 }
@@ -1,13 +1,13 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 struct ChannelFrameInfo {
     header: Header,
     class_id: ClassID,
     method_id: ChannelMethodID,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Open {
     frame_info: ChannelFrameInfo,
     reserved_1: ShortString,
@@ -34,32 +34,152 @@ impl Open {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct OpenOk {
     frame_info: ChannelFrameInfo,
-    // Is this channel? Pika thinks so but looks like - to me
-    pub reserved_1: u16,
+    // Deprecated channel id, always an empty long string
+    reserved_1: LongString,
 }
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Flow {
     frame_info: ChannelFrameInfo,
-    active: Bits,
+    active: Bits<1>,
 }
-#[derive(Debug, Clone, bincode::Encode)]
+
+impl Flow {
+    pub fn active(&self) -> bool {
+        self.active[0]
+    }
+}
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct FlowOk {
     frame_info: ChannelFrameInfo,
-    active: Bits,
+    active: Bits<1>,
 }
-#[derive(Debug, Clone, bincode::Encode)]
+
+impl FlowOk {
+    pub fn new(channel_id: u16, active: bool) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Channel;
+        let method_id = ChannelMethodID::FlowOk;
+        let frame_info = ChannelFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self {
+            frame_info,
+            active: active.into(),
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.active[0]
+    }
+}
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Close {
     frame_info: ChannelFrameInfo,
-    reply_code: u16,
-    reply_text: ShortString,
-    closing_class_id: u16,
-    closing_method_id: u16,
-    reserved_1: ShortString,
+    pub reply_code: u16,
+    pub reply_text: ShortString,
+    pub closing_class_id: u16,
+    pub closing_method_id: u16,
+}
+
+impl Close {
+    pub fn new(
+        channel_id: u16,
+        reply_code: u16,
+        reply_text: &str,
+        closing_class_id: u16,
+        closing_method_id: u16,
+    ) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Channel;
+        let method_id = ChannelMethodID::Close;
+        let frame_info = ChannelFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self {
+            frame_info,
+            reply_code,
+            reply_text: reply_text.into(),
+            closing_class_id,
+            closing_method_id,
+        }
+    }
 }
-#[derive(Debug, Clone, bincode::Encode)]
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct CloseOk {
     frame_info: ChannelFrameInfo,
 }
+
+impl CloseOk {
+    pub fn new(channel_id: u16) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Channel;
+        let method_id = ChannelMethodID::CloseOk;
+        let frame_info = ChannelFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self { frame_info }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    fn frame_info(channel_id: u16, method_id: ChannelMethodID) -> ChannelFrameInfo {
+        ChannelFrameInfo {
+            header: method_header(channel_id),
+            class_id: ClassID::Channel,
+            method_id,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            channel_id: u16,
+            active in bits::<1>(),
+            reply_code: u16,
+            reply_text in short_string(),
+            closing_ids: (u16, u16),
+        ) {
+            let info = |method_id| frame_info(channel_id, method_id);
+            assert_round_trip(Open::new(channel_id))?;
+            assert_round_trip(OpenOk { frame_info: info(ChannelMethodID::OpenOk), reserved_1: LongString::default() })?;
+            assert_round_trip(Flow { frame_info: info(ChannelMethodID::Flow), active })?;
+            assert_round_trip(FlowOk::new(channel_id, active[0]))?;
+            assert_round_trip(Close {
+                frame_info: info(ChannelMethodID::Close),
+                reply_code,
+                reply_text,
+                closing_class_id: closing_ids.0,
+                closing_method_id: closing_ids.1,
+            })?;
+            assert_round_trip(CloseOk::new(channel_id))?;
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::encde::*;
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct ConfirmFrameInfo {
+    header: Header,
+    class_id: ClassID,
+    method_id: ConfirmMethodID,
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Select {
+    frame_info: ConfirmFrameInfo,
+    no_wait: Bits<1>,
+}
+
+impl Select {
+    pub fn new(channel_id: u16, no_wait: bool) -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id,
+            size: 0,
+        };
+        let class_id = ClassID::Confirm;
+        let method_id = ConfirmMethodID::Select;
+        let frame_info = ConfirmFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self {
+            frame_info,
+            no_wait: no_wait.into(),
+        }
+    }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct SelectOk {
+    frame_info: ConfirmFrameInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(channel_id: u16, no_wait: bool) {
+            assert_round_trip(Select::new(channel_id, no_wait))?;
+            assert_round_trip(SelectOk {
+                frame_info: ConfirmFrameInfo {
+                    header: method_header(channel_id),
+                    class_id: ClassID::Confirm,
+                    method_id: ConfirmMethodID::SelectOk,
+                },
+            })?;
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::encde::*;
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 struct ConnectionFrameInfo {
     header: Header,
     class_id: ClassID,
@@ -8,7 +8,7 @@ struct ConnectionFrameInfo {
 
 const GLOBAL_CHANNEL: u16 = 0;
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct ProtocolHeader {
     a: u8,
     m: u8,
@@ -35,7 +35,7 @@ impl ProtocolHeader {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Start {
     frame_info: ConnectionFrameInfo,
     version_major: u8,
@@ -45,7 +45,7 @@ pub struct Start {
     pub locales: LongString,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct StartOk {
     frame_info: ConnectionFrameInfo,
     client_properties: Table,
@@ -55,31 +55,22 @@ pub struct StartOk {
 }
 
 impl StartOk {
-    pub fn new(mechanism: &str, response: &str, locale: &str) -> Self {
-        let capabilites: Table = Table(vec![
-            ("authentication_failure_close".into(), Field::Bool(true)),
-            ("basic.nack".into(), Field::Bool(true)),
-            ("connection.blocked".into(), Field::Bool(true)),
-            ("consumer_cancel_notify".into(), Field::Bool(true)),
-            ("publisher_confirms".into(), Field::Bool(true)),
-        ]);
-
-        let client_properties: Table = Table(vec![
-            (
-                "product".to_owned(),
-                Field::LS(LongString("Pika Python Client Library".into())),
-            ),
-            (
-                "platform".into(),
-                Field::LS(LongString("Python 3.11.6".into())),
-            ),
-            ("capabilities".into(), Field::T(capabilites)),
-            (
-                "information".into(),
-                Field::LS(LongString("See http://pika.rtfd.org".into())),
-            ),
-            ("version".into(), Field::LS(LongString("2.0.0a0".into()))),
-        ]);
+    pub fn new(mechanism: &str, response: &[u8], locale: &str) -> Self {
+        let capabilites = crate::table! {
+            "authentication_failure_close" => true,
+            "basic.nack" => true,
+            "connection.blocked" => true,
+            "consumer_cancel_notify" => true,
+            "publisher_confirms" => true,
+        };
+
+        let client_properties = crate::table! {
+            "product" => "Pika Python Client Library",
+            "platform" => "Python 3.11.6",
+            "capabilities" => capabilites,
+            "information" => "See http://pika.rtfd.org",
+            "version" => "2.0.0a0",
+        };
         let header = Header {
             frame_type: FrameType::Method,
             channel_id: GLOBAL_CHANNEL,
@@ -103,18 +94,18 @@ impl StartOk {
     }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Secure {
     frame_info: ConnectionFrameInfo,
     challenge: LongString,
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct SecureOk {
     frame_info: ConnectionFrameInfo,
     response: LongString,
 }
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Tune {
     frame_info: ConnectionFrameInfo,
     pub channel_max: u16,
@@ -122,7 +113,7 @@ pub struct Tune {
     pub heartbeat: u16,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct TuneOk {
     frame_info: ConnectionFrameInfo,
     channel_max: u16,
@@ -154,7 +145,7 @@ impl TuneOk {
     }
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Open {
     frame_info: ConnectionFrameInfo,
     pub virtual_host: ShortString,
@@ -186,19 +177,19 @@ impl Open {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct OpenOk {
     frame_info: ConnectionFrameInfo,
     reserved_1: ShortString,
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Close {
     frame_info: ConnectionFrameInfo,
-    reply_code: u16,
-    reply_text: ShortString,
-    closing_class_id: u16,
-    closing_method_id: u16,
+    pub reply_code: u16,
+    pub reply_text: ShortString,
+    pub closing_class_id: u16,
+    pub closing_method_id: u16,
 }
 
 impl Close {
@@ -230,7 +221,103 @@ impl Close {
     }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct CloseOk {
     frame_info: ConnectionFrameInfo,
 }
+
+impl CloseOk {
+    pub fn new() -> Self {
+        let header = Header {
+            frame_type: FrameType::Method,
+            channel_id: GLOBAL_CHANNEL,
+            size: 0,
+        };
+        let class_id = ClassID::Connection;
+        let method_id = ConnectionMethodID::CloseOk;
+        let frame_info = ConnectionFrameInfo {
+            header,
+            class_id,
+            method_id,
+        };
+        Self { frame_info }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Blocked {
+    frame_info: ConnectionFrameInfo,
+    pub reason: ShortString,
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct Unblocked {
+    frame_info: ConnectionFrameInfo,
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    fn frame_info(method_id: ConnectionMethodID) -> ConnectionFrameInfo {
+        ConnectionFrameInfo {
+            header: method_header(GLOBAL_CHANNEL),
+            class_id: ClassID::Connection,
+            method_id,
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            text in string(),
+            bytes in long_string(),
+            server_properties in table(),
+            tune: (u16, u32, u16),
+            close: (u16, u16, u16),
+        ) {
+            let (channel_max, frame_max, heartbeat) = tune;
+            let (reply_code, closing_class_id, closing_method_id) = close;
+            assert_round_trip(ProtocolHeader::new())?;
+            assert_round_trip(Start {
+                frame_info: frame_info(ConnectionMethodID::Start),
+                version_major: 0,
+                version_minor: 9,
+                server_properties,
+                mechanisms: bytes.clone(),
+                locales: bytes.clone(),
+            })?;
+            assert_round_trip(StartOk::new(&text, &bytes, &text))?;
+            assert_round_trip(Secure {
+                frame_info: frame_info(ConnectionMethodID::Secure),
+                challenge: bytes.clone(),
+            })?;
+            assert_round_trip(SecureOk {
+                frame_info: frame_info(ConnectionMethodID::SecureOk),
+                response: bytes,
+            })?;
+            assert_round_trip(Tune {
+                frame_info: frame_info(ConnectionMethodID::Tune),
+                channel_max,
+                frame_max,
+                heartbeat,
+            })?;
+            assert_round_trip(TuneOk::new(channel_max, frame_max, heartbeat))?;
+            assert_round_trip(Open::new(&text))?;
+            assert_round_trip(OpenOk {
+                frame_info: frame_info(ConnectionMethodID::OpenOk),
+                reserved_1: text.as_str().into(),
+            })?;
+            assert_round_trip(Close::new(reply_code, &text, closing_class_id, closing_method_id))?;
+            assert_round_trip(CloseOk::new())?;
+            assert_round_trip(Blocked {
+                frame_info: frame_info(ConnectionMethodID::Blocked),
+                reason: text.as_str().into(),
+            })?;
+            assert_round_trip(Unblocked { frame_info: frame_info(ConnectionMethodID::Unblocked) })?;
+        }
+    }
+}
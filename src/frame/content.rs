@@ -1,11 +1,11 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Decode, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Content {
     header: Header,
     class_id: ClassID,
     weight: u16,
-    size: u64,
+    pub size: u64,
     pub properties: Properties,
 }
 
@@ -26,4 +26,39 @@ impl Content {
             properties,
         }
     }
+
+    // The body size of a received content header, which comes before the properties
+    // and so can be read even when they fail to decode
+    pub fn body_size(frame: &[u8]) -> Result<u64, bincode::error::DecodeError> {
+        #[derive(bincode::Decode)]
+        struct Prefix {
+            header: Header,
+            class_id: ClassID,
+            weight: u16,
+            size: u64,
+        }
+        let prefix: Prefix = decode_frame(frame)?;
+        if prefix.header.frame_type != FrameType::Header {
+            return Err(bincode::error::DecodeError::OtherString(format!(
+                "Expected a content header frame, got {:?}",
+                prefix.header.frame_type
+            )));
+        }
+        Ok(prefix.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(channel_id: u16, size: u64, properties in properties()) {
+            assert_round_trip(Content::new(channel_id, size, properties))?;
+        }
+    }
 }
@@ -1,19 +1,19 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 struct ExchangeFrameInfo {
     header: Header,
     class_id: ClassID,
     method_id: ExchangeMethodID,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Declare {
     frame_info: ExchangeFrameInfo,
     reserved_1: u16,
     exchange: ShortString,
     exchange_type: ExchangeType,
-    passive_durable: Bits, // There are two reserved in here, be careful
+    bits: Bits<5>, // passive, durable, auto_delete, internal, no_wait
     arguments: Table,
 }
 
@@ -36,27 +36,43 @@ impl Declare {
             reserved_1: RESERVED16,
             exchange: ShortString(exchange),
             exchange_type,
-            passive_durable: Bits::default(),
+            bits: Bits::default(),
             arguments: Table::default(),
         }
     }
+
+    pub fn passive(&self) -> bool {
+        self.bits[0]
+    }
+    pub fn durable(&self) -> bool {
+        self.bits[1]
+    }
+    pub fn auto_delete(&self) -> bool {
+        self.bits[2]
+    }
+    pub fn internal(&self) -> bool {
+        self.bits[3]
+    }
+    pub fn no_wait(&self) -> bool {
+        self.bits[4]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct DeclareOk {
     frame_info: ExchangeFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Delete {
     frame_info: ExchangeFrameInfo,
     reserved_1: u16,
     exchange: ShortString,
-    ifunused_nowait: Bits,
+    ifunused_nowait: Bits<2>,
 }
 
 impl Delete {
-    pub fn new(channel_id: u16, exchange: &str) -> Self {
+    pub fn new(channel_id: u16, exchange: &str, if_unused: bool, no_wait: bool) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
             channel_id,
@@ -73,32 +89,45 @@ impl Delete {
             frame_info,
             reserved_1: RESERVED16,
             exchange: exchange.into(),
-            ifunused_nowait: Bits::default(),
+            ifunused_nowait: (if_unused, no_wait).into(),
         }
     }
+
+    pub fn if_unused(&self) -> bool {
+        self.ifunused_nowait[0]
+    }
+    pub fn no_wait(&self) -> bool {
+        self.ifunused_nowait[1]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct DeleteOk {
     frame_info: ExchangeFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Bind {
     frame_info: ExchangeFrameInfo,
     reserved_1: u16,
     destination: ShortString,
     source: ShortString,
     routing_key: ShortString,
-    no_wait: Bits,
+    no_wait: Bits<1>,
     arguments: Table,
 }
 
 impl Bind {
-    pub fn new(destination: &str, source: &str, routing_key: &str, no_wait: bool) -> Self {
+    pub fn new(
+        channel_id: u16,
+        destination: &str,
+        source: &str,
+        routing_key: &str,
+        no_wait: bool,
+    ) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Exchange;
@@ -114,32 +143,42 @@ impl Bind {
             destination: ShortString(destination.into()),
             source: ShortString(source.into()),
             routing_key: ShortString(routing_key.into()),
-            no_wait: Bits(vec![no_wait.into()]),
+            no_wait: no_wait.into(),
             arguments: Table(vec![]),
         }
     }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
 }
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct BindOk {
     frame_info: ExchangeFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Unbind {
     frame_info: ExchangeFrameInfo,
     reserved_1: u16,
     destination: ShortString,
     source: ShortString,
     routing_key: ShortString,
-    no_wait: Bits,
+    no_wait: Bits<1>,
     arguments: Table,
 }
 
 impl Unbind {
-    pub fn new(destination: &str, source: &str, routing_key: &str, no_wait: bool) -> Self {
+    pub fn new(
+        channel_id: u16,
+        destination: &str,
+        source: &str,
+        routing_key: &str,
+        no_wait: bool,
+    ) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Exchange;
@@ -155,13 +194,52 @@ impl Unbind {
             destination: ShortString(destination.into()),
             source: ShortString(source.into()),
             routing_key: ShortString(routing_key.into()),
-            no_wait: Bits(vec![no_wait.into()]),
+            no_wait: no_wait.into(),
             arguments: Table(vec![]),
         }
     }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct UnbindOk {
     frame_info: ExchangeFrameInfo,
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            channel_id: u16,
+            exchange in string(),
+            source in string(),
+            routing_key in string(),
+            exchange_type in exchange_type(),
+            flags: [bool; 2],
+        ) {
+            let info = |method_id| ExchangeFrameInfo {
+                header: method_header(channel_id),
+                class_id: ClassID::Exchange,
+                method_id,
+            };
+            let [a, b] = flags;
+            assert_round_trip(Declare::new(channel_id, exchange.clone(), exchange_type))?;
+            assert_round_trip(DeclareOk { frame_info: info(ExchangeMethodID::DeclareOk) })?;
+            assert_round_trip(Delete::new(channel_id, &exchange, a, b))?;
+            assert_round_trip(DeleteOk { frame_info: info(ExchangeMethodID::DeleteOk) })?;
+            assert_round_trip(Bind::new(channel_id, &exchange, &source, &routing_key, a))?;
+            assert_round_trip(BindOk { frame_info: info(ExchangeMethodID::BindOk) })?;
+            assert_round_trip(Unbind::new(channel_id, &exchange, &source, &routing_key, a))?;
+            assert_round_trip(UnbindOk { frame_info: info(ExchangeMethodID::UnbindOk) })?;
+        }
+    }
+}
@@ -0,0 +1,463 @@
+// Conformance tests against the annotated frames in testdata/golden. Frames the
+// client sends have to be reproduced byte for byte, frames the broker sends have to
+// decode to the expected values and encode back to the same bytes.
+use bytes::Bytes;
+
+use super::*;
+
+macro_rules! golden {
+    ($name:literal) => {
+        frames(include_str!(concat!(
+            "../../testdata/golden/",
+            $name,
+            ".hex"
+        )))
+    };
+}
+
+// The hex digits of a golden file, ignoring whitespace and `#` comments
+fn bytes(hex: &str) -> Vec<u8> {
+    hex.lines()
+        .flat_map(|line| line.split('#').next().unwrap_or("").split_whitespace())
+        .flat_map(|token| {
+            (0..token.len())
+                .step_by(2)
+                .map(move |i| u8::from_str_radix(&token[i..i + 2], 16).unwrap())
+        })
+        .collect()
+}
+
+// Splits a golden file into frames, checking each size field against its content
+fn frames(hex: &str) -> Vec<Bytes> {
+    let bytes = Bytes::from(bytes(hex));
+    let mut frames = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let header: Header = decode_frame(&bytes[start..]).unwrap();
+        let end = start + HEADER_SIZE + header.size as usize + 1;
+        assert_eq!(
+            bytes[end - 1],
+            FRAME_END,
+            "Frame size does not match its content"
+        );
+        frames.push(bytes.slice(start..end));
+        start = end;
+    }
+    frames
+}
+
+fn encode<E: bincode::Encode>(frame: E) -> Bytes {
+    encode_frame(frame).unwrap().into()
+}
+
+// Server frames carry no state the decoder drops, so they encode back as received
+fn assert_reencodes<T: bincode::Encode + bincode::Decode>(frame: &Bytes) {
+    let decoded: T = decode_frame(frame).unwrap();
+    assert_eq!(encode(decoded), frame);
+}
+
+#[test]
+fn test_handshake() {
+    let protocol_header = bytes(include_str!("../../testdata/golden/protocol_header.hex"));
+    assert_eq!(
+        encode_frame_static(connection::ProtocolHeader::new()).unwrap(),
+        protocol_header
+    );
+
+    let [start] = &golden!("connection_start")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Connection(ConnectionFrame::Start(start_frame)) =
+        AmqpFrame::decode(start).unwrap()
+    else {
+        panic!("Expected connection.start");
+    };
+    assert_eq!(start_frame.mechanisms.as_str(), Some("AMQPLAIN PLAIN"));
+    assert_eq!(start_frame.locales.as_str(), Some("en_US"));
+    assert_reencodes::<connection::Start>(start);
+
+    assert_eq!(
+        golden!("connection_start_ok"),
+        [encode(connection::StartOk::new(
+            "PLAIN",
+            b"\0guest\0guest",
+            "en_US"
+        ))]
+    );
+
+    let [tune] = &golden!("connection_tune")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Connection(ConnectionFrame::Tune(tune_frame)) = AmqpFrame::decode(tune).unwrap()
+    else {
+        panic!("Expected connection.tune");
+    };
+    assert_eq!(
+        (
+            tune_frame.channel_max,
+            tune_frame.frame_max,
+            tune_frame.heartbeat
+        ),
+        (2047, 131072, 60)
+    );
+    assert_reencodes::<connection::Tune>(tune);
+
+    assert_eq!(
+        golden!("connection_tune_ok"),
+        [encode(connection::TuneOk::new(2047, 131072, 60))]
+    );
+    assert_eq!(
+        golden!("connection_open"),
+        [encode(connection::Open::new("/"))]
+    );
+
+    let [open_ok] = &golden!("connection_open_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(open_ok).unwrap(),
+        AmqpFrame::Connection(ConnectionFrame::OpenOk(_))
+    ));
+    assert_reencodes::<connection::OpenOk>(open_ok);
+}
+
+#[test]
+fn test_channel_setup() {
+    assert_eq!(golden!("channel_open"), [encode(channel::Open::new(2))]);
+
+    let [open_ok] = &golden!("channel_open_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(open_ok).unwrap(),
+        AmqpFrame::Channel(ChannelFrame::OpenOk(_))
+    ));
+    assert_reencodes::<channel::OpenOk>(open_ok);
+
+    assert_eq!(
+        golden!("confirm_select"),
+        [encode(confirm::Select::new(2, false))]
+    );
+    let [select_ok] = &golden!("confirm_select_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(select_ok).unwrap(),
+        AmqpFrame::Confirm(ConfirmFrame::SelectOk(_))
+    ));
+    assert_reencodes::<confirm::SelectOk>(select_ok);
+
+    assert_eq!(
+        golden!("basic_qos"),
+        [encode(basic::QualityOfService::new(2, 0, 10, false))]
+    );
+}
+
+#[test]
+fn test_declares() {
+    assert_eq!(
+        golden!("exchange_declare"),
+        [encode(exchange::Declare::new(
+            2,
+            "events".into(),
+            ExchangeType::Topic
+        ))]
+    );
+    let [declare_ok] = &golden!("exchange_declare_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(declare_ok).unwrap(),
+        AmqpFrame::Exchange(ExchangeFrame::DeclareOk(_))
+    ));
+    assert_reencodes::<exchange::DeclareOk>(declare_ok);
+    // Declare::new leaves every bit off, so only decoding is checked with bits set
+    let [declare] = &golden!("exchange_declare_durable")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Exchange(ExchangeFrame::Declare(declare_frame)) =
+        AmqpFrame::decode(declare).unwrap()
+    else {
+        panic!("Expected exchange.declare");
+    };
+    assert!(!declare_frame.passive());
+    assert!(declare_frame.durable());
+    assert!(!declare_frame.auto_delete());
+    assert!(declare_frame.internal());
+    assert!(!declare_frame.no_wait());
+    assert_reencodes::<exchange::Declare>(declare);
+
+    assert_eq!(
+        golden!("queue_declare"),
+        [encode(queue::Declare::new(
+            2, "orders", false, true, false, true, false
+        ))]
+    );
+    let [declare_ok] = &golden!("queue_declare_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Queue(QueueFrame::DeclareOk(declare_ok_frame)) =
+        AmqpFrame::decode(declare_ok).unwrap()
+    else {
+        panic!("Expected queue.declare-ok");
+    };
+    assert_eq!(declare_ok_frame.queue_name.as_str(), "orders");
+    assert_eq!(declare_ok_frame.message_count, 3);
+    assert_eq!(declare_ok_frame.consumer_count, 1);
+    assert_reencodes::<queue::DeclareOk>(declare_ok);
+}
+
+#[test]
+fn test_publish() {
+    let properties = Properties::builder()
+        .content_type("application/json".into())
+        .header("x-retry", 1)
+        .delivery_mode(properties::DeliveryMode::Persistent)
+        .build();
+    let body = br#"{"order_id":1234}"#;
+    assert_eq!(
+        golden!("basic_publish"),
+        [
+            encode(basic::Publish::new(
+                2,
+                "events",
+                "order.created",
+                true,
+                false
+            )),
+            encode(content::Content::new(2, body.len() as u64, properties)),
+            encode(body::Body::new(2, RawBytes(Bytes::from_static(body)))),
+        ]
+    );
+
+    let [ack] = &golden!("basic_ack_confirm")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Basic(BasicFrame::Ack(ack_frame)) = AmqpFrame::decode(ack).unwrap() else {
+        panic!("Expected basic.ack");
+    };
+    assert!(ack_frame.multiple());
+    assert_reencodes::<basic::Ack>(ack);
+
+    let [nack] = &golden!("basic_nack_confirm")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(nack).unwrap(),
+        AmqpFrame::Basic(BasicFrame::Nack(_))
+    ));
+    // A broker nack has the same layout as one sent by the client
+    assert_eq!(nack, &encode(basic::Nack::new(2, 4, false, false)));
+}
+
+#[test]
+fn test_return() {
+    let [method, header, body] = &golden!("basic_return")[..] else {
+        panic!("Expected a method, header and body frame");
+    };
+    let AmqpFrame::Basic(BasicFrame::Return(return_frame)) = AmqpFrame::decode(method).unwrap()
+    else {
+        panic!("Expected basic.return");
+    };
+    assert_eq!(return_frame.reply_code, 312);
+    assert_eq!(return_frame.reply_text.as_str(), "NO_ROUTE");
+    assert_eq!(return_frame.routing_key.as_str(), "order.unknown");
+    assert_reencodes::<basic::Return>(method);
+
+    let AmqpFrame::Content(content_header) = AmqpFrame::decode(header).unwrap() else {
+        panic!("Expected a content header");
+    };
+    assert_eq!(content_header.size, 2);
+    assert_eq!(content_header.properties.content_type(), Some("text/plain"));
+    assert_reencodes::<content::Content>(header);
+
+    assert_eq!(body::Body::payload(body).unwrap(), &b"hi"[..]);
+}
+
+#[test]
+fn test_consume_and_deliver() {
+    assert_eq!(
+        golden!("basic_consume"),
+        [encode(basic::Consume::new(
+            2,
+            "orders",
+            "ctag-1",
+            false,
+            true,
+            true,
+            false,
+            crate::table! { "x-priority" => 5 },
+        ))]
+    );
+    let [consume_ok] = &golden!("basic_consume_ok")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Basic(BasicFrame::ConsumeOk(consume_ok_frame)) =
+        AmqpFrame::decode(consume_ok).unwrap()
+    else {
+        panic!("Expected basic.consume-ok");
+    };
+    assert_eq!(consume_ok_frame.consumer_tag.as_str(), "ctag-1");
+    assert_reencodes::<basic::ConsumeOk>(consume_ok);
+
+    let [method, header, bodies @ ..] = &golden!("basic_deliver")[..] else {
+        panic!("Expected a method, header and body frames");
+    };
+    let AmqpFrame::Basic(BasicFrame::Deliver(deliver)) = AmqpFrame::decode(method).unwrap() else {
+        panic!("Expected basic.deliver");
+    };
+    assert_eq!(deliver.consumer_tag.as_str(), "ctag-1");
+    assert_eq!(deliver.delivery_tag, 42);
+    assert!(deliver.redelivered());
+    assert_eq!(deliver.exchange.as_str(), "events");
+    assert_eq!(deliver.routing_key.as_str(), "order.created");
+    assert_reencodes::<basic::Deliver>(method);
+
+    let AmqpFrame::Content(content_header) = AmqpFrame::decode(header).unwrap() else {
+        panic!("Expected a content header");
+    };
+    let properties = &content_header.properties;
+    assert_eq!(properties.content_type(), Some("application/json"));
+    assert_eq!(
+        properties.delivery_mode(),
+        Some(&properties::DeliveryMode::Persistent)
+    );
+    assert_eq!(properties.correlation_id(), Some("req-7"));
+    assert_eq!(properties.reply_to(), Some("amq.rabbitmq.reply-to"));
+    assert_eq!(properties.message_id(), Some("msg-42"));
+    assert_eq!(properties.timestamp(), Some(1700000000));
+    assert_eq!(properties.app_id(), Some("orders-service"));
+    let headers = properties.headers().unwrap();
+    assert_eq!(headers.get_str("x-first-death-exchange"), Some("events"));
+    assert_eq!(headers.get_i64("x-retry"), Some(2));
+    assert_eq!(headers.get_i64("x-received-at"), Some(1700000000123));
+    assert_eq!(headers.get_bool("x-redelivered"), Some(false));
+    assert_eq!(headers.get("x-score"), Some(&Field::F64(0.75)));
+    let trace = headers.get_table("x-trace").unwrap();
+    assert_eq!(trace.get_str("span"), Some("a1b2c3"));
+    assert_eq!(trace.get_bool("sampled"), Some(true));
+    assert_eq!(
+        headers.get("x-tags"),
+        Some(&Field::A(vec!["eu".into(), "priority".into(), 7.into()]))
+    );
+    assert_reencodes::<content::Content>(header);
+
+    let payloads = bodies
+        .iter()
+        .map(|body| body::Body::payload(body).unwrap())
+        .collect::<Vec<_>>();
+    let body = body::Body::join(payloads);
+    assert_eq!(body.len() as u64, content_header.size);
+    assert_eq!(body, &br#"{"order_id":1234,"v":2}"#[..]);
+}
+
+#[test]
+fn test_dead_lettered_delivery() {
+    let [method, header, body] = &golden!("basic_deliver_dead_lettered")[..] else {
+        panic!("Expected a method, header and body frame");
+    };
+    let AmqpFrame::Basic(BasicFrame::Deliver(deliver)) = AmqpFrame::decode(method).unwrap() else {
+        panic!("Expected basic.deliver");
+    };
+    assert_eq!(deliver.exchange.as_str(), "orders.dlx");
+    assert!(!deliver.redelivered());
+    assert_reencodes::<basic::Deliver>(method);
+
+    let AmqpFrame::Content(content_header) = AmqpFrame::decode(header).unwrap() else {
+        panic!("Expected a content header");
+    };
+    let headers = content_header.properties.headers().unwrap();
+    assert_eq!(headers.get_str("x-first-death-queue"), Some("orders"));
+    assert_eq!(headers.get_str("x-first-death-reason"), Some("rejected"));
+    let Some(Field::A(deaths)) = headers.get("x-death") else {
+        panic!("Expected x-death to be an array");
+    };
+    let [Field::T(death)] = &deaths[..] else {
+        panic!("Expected a single x-death table");
+    };
+    assert_eq!(death.get_i64("count"), Some(1));
+    assert_eq!(death.get_str("reason"), Some("rejected"));
+    assert_eq!(death.get_str("queue"), Some("orders"));
+    assert_eq!(death.get_timestamp("time"), Some(1700000000));
+    assert_eq!(death.get_str("exchange"), Some("events"));
+    assert_eq!(
+        death.get("routing-keys"),
+        Some(&Field::A(vec!["order.created".into()]))
+    );
+    assert_reencodes::<content::Content>(header);
+
+    assert_eq!(body::Body::payload(body).unwrap(), &b"{}"[..]);
+}
+
+#[test]
+fn test_acknowledgements() {
+    assert_eq!(golden!("basic_ack"), [encode(basic::Ack::new(2, 7))]);
+    assert_eq!(
+        golden!("basic_nack"),
+        [encode(basic::Nack::new(2, 7, false, true))]
+    );
+    assert_eq!(
+        golden!("basic_reject"),
+        [encode(basic::Reject::new(2, 8, true))]
+    );
+
+    let [get_empty] = &golden!("basic_get_empty")[..] else {
+        panic!("Expected a single frame");
+    };
+    assert!(matches!(
+        AmqpFrame::decode(get_empty).unwrap(),
+        AmqpFrame::Basic(BasicFrame::GetEmpty(_))
+    ));
+    assert_reencodes::<basic::GetEmpty>(get_empty);
+}
+
+#[test]
+fn test_close_and_blocked() {
+    let [close] = &golden!("channel_close")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Channel(ChannelFrame::Close(close_frame)) = AmqpFrame::decode(close).unwrap()
+    else {
+        panic!("Expected channel.close");
+    };
+    assert_eq!(close_frame.reply_code, 404);
+    assert_eq!(
+        close_frame.reply_text.as_str(),
+        "NOT_FOUND - no queue 'missing' in vhost '/'"
+    );
+    assert_eq!(
+        (close_frame.closing_class_id, close_frame.closing_method_id),
+        (50, 10)
+    );
+    assert_reencodes::<channel::Close>(close);
+    assert_eq!(
+        golden!("channel_close_ok"),
+        [encode(channel::CloseOk::new(2))]
+    );
+
+    let [close] = &golden!("connection_close_forced")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Connection(ConnectionFrame::Close(close_frame)) =
+        AmqpFrame::decode(close).unwrap()
+    else {
+        panic!("Expected connection.close");
+    };
+    assert_eq!(close_frame.reply_code, 320);
+    assert_reencodes::<connection::Close>(close);
+    assert_eq!(
+        golden!("connection_close"),
+        [encode(connection::Close::new(200, "Goodbye", 0, 0))]
+    );
+
+    let [blocked] = &golden!("connection_blocked")[..] else {
+        panic!("Expected a single frame");
+    };
+    let AmqpFrame::Connection(ConnectionFrame::Blocked(blocked_frame)) =
+        AmqpFrame::decode(blocked).unwrap()
+    else {
+        panic!("Expected connection.blocked");
+    };
+    assert_eq!(blocked_frame.reason.as_str(), "low on memory");
+    assert_reencodes::<connection::Blocked>(blocked);
+}
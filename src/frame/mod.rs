@@ -1,8 +1,456 @@
+use crate::encde::*;
+
 pub mod basic;
 pub mod body;
 pub mod channel;
+pub mod confirm;
 pub mod connection;
 pub mod content;
 pub mod exchange;
+#[cfg(test)]
+mod golden;
 pub mod queue;
 pub mod transaction;
+
+#[derive(Debug, Clone, PartialEq, bincode::Decode)]
+pub struct MethodFrameInfo {
+    pub header: Header,
+    pub method: Method,
+}
+
+// Any incoming frame, decoded by peeking the header and, for method frames, the
+// class and method id
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmqpFrame {
+    Connection(ConnectionFrame),
+    Channel(ChannelFrame),
+    Exchange(ExchangeFrame),
+    Queue(QueueFrame),
+    Basic(BasicFrame),
+    Confirm(ConfirmFrame),
+    Transaction(TransactionFrame),
+    Content(Box<content::Content>),
+    Body(body::Body),
+    Heartbeat,
+}
+
+impl AmqpFrame {
+    pub fn decode(buffer: &[u8]) -> Result<Self, bincode::error::DecodeError> {
+        let header: Header = decode_frame(buffer)?;
+        Ok(match header.frame_type {
+            FrameType::Method => {
+                let frame_info: MethodFrameInfo = decode_frame(buffer)?;
+                match frame_info.method {
+                    Method::Connection(method_id) => {
+                        Self::Connection(ConnectionFrame::decode(method_id, buffer)?)
+                    }
+                    Method::Channel(method_id) => {
+                        Self::Channel(ChannelFrame::decode(method_id, buffer)?)
+                    }
+                    Method::Exchange(method_id) => {
+                        Self::Exchange(ExchangeFrame::decode(method_id, buffer)?)
+                    }
+                    Method::Queue(method_id) => Self::Queue(QueueFrame::decode(method_id, buffer)?),
+                    Method::Basic(method_id) => Self::Basic(BasicFrame::decode(method_id, buffer)?),
+                    Method::Confirm(method_id) => {
+                        Self::Confirm(ConfirmFrame::decode(method_id, buffer)?)
+                    }
+                    Method::Transaction(method_id) => {
+                        Self::Transaction(TransactionFrame::decode(method_id, buffer)?)
+                    }
+                }
+            }
+            FrameType::Header => Self::Content(Box::new(decode_frame(buffer)?)),
+            FrameType::Body => Self::Body(decode_frame(buffer)?),
+            FrameType::Heartbeat => Self::Heartbeat,
+            FrameType::FatalError => {
+                return Err(bincode::error::DecodeError::OtherString(format!(
+                    "Unknown frame type {}",
+                    buffer[0]
+                )))
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionFrame {
+    Start(connection::Start),
+    StartOk(connection::StartOk),
+    Secure(connection::Secure),
+    SecureOk(connection::SecureOk),
+    Tune(connection::Tune),
+    TuneOk(connection::TuneOk),
+    Open(connection::Open),
+    OpenOk(connection::OpenOk),
+    Close(connection::Close),
+    CloseOk(connection::CloseOk),
+    Blocked(connection::Blocked),
+    Unblocked(connection::Unblocked),
+}
+
+impl ConnectionFrame {
+    fn decode(
+        method_id: ConnectionMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            ConnectionMethodID::Start => Self::Start(decode_frame(buffer)?),
+            ConnectionMethodID::StartOk => Self::StartOk(decode_frame(buffer)?),
+            ConnectionMethodID::Secure => Self::Secure(decode_frame(buffer)?),
+            ConnectionMethodID::SecureOk => Self::SecureOk(decode_frame(buffer)?),
+            ConnectionMethodID::Tune => Self::Tune(decode_frame(buffer)?),
+            ConnectionMethodID::TuneOk => Self::TuneOk(decode_frame(buffer)?),
+            ConnectionMethodID::Open => Self::Open(decode_frame(buffer)?),
+            ConnectionMethodID::OpenOk => Self::OpenOk(decode_frame(buffer)?),
+            ConnectionMethodID::Close => Self::Close(decode_frame(buffer)?),
+            ConnectionMethodID::CloseOk => Self::CloseOk(decode_frame(buffer)?),
+            ConnectionMethodID::Blocked => Self::Blocked(decode_frame(buffer)?),
+            ConnectionMethodID::Unblocked => Self::Unblocked(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelFrame {
+    Open(channel::Open),
+    OpenOk(channel::OpenOk),
+    Flow(channel::Flow),
+    FlowOk(channel::FlowOk),
+    Close(channel::Close),
+    CloseOk(channel::CloseOk),
+}
+
+impl ChannelFrame {
+    fn decode(
+        method_id: ChannelMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            ChannelMethodID::Open => Self::Open(decode_frame(buffer)?),
+            ChannelMethodID::OpenOk => Self::OpenOk(decode_frame(buffer)?),
+            ChannelMethodID::Flow => Self::Flow(decode_frame(buffer)?),
+            ChannelMethodID::FlowOk => Self::FlowOk(decode_frame(buffer)?),
+            ChannelMethodID::Close => Self::Close(decode_frame(buffer)?),
+            ChannelMethodID::CloseOk => Self::CloseOk(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExchangeFrame {
+    Declare(exchange::Declare),
+    DeclareOk(exchange::DeclareOk),
+    Delete(exchange::Delete),
+    DeleteOk(exchange::DeleteOk),
+    Bind(exchange::Bind),
+    BindOk(exchange::BindOk),
+    Unbind(exchange::Unbind),
+    UnbindOk(exchange::UnbindOk),
+}
+
+impl ExchangeFrame {
+    fn decode(
+        method_id: ExchangeMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            ExchangeMethodID::Declare => Self::Declare(decode_frame(buffer)?),
+            ExchangeMethodID::DeclareOk => Self::DeclareOk(decode_frame(buffer)?),
+            ExchangeMethodID::Delete => Self::Delete(decode_frame(buffer)?),
+            ExchangeMethodID::DeleteOk => Self::DeleteOk(decode_frame(buffer)?),
+            ExchangeMethodID::Bind => Self::Bind(decode_frame(buffer)?),
+            ExchangeMethodID::BindOk => Self::BindOk(decode_frame(buffer)?),
+            ExchangeMethodID::Unbind => Self::Unbind(decode_frame(buffer)?),
+            ExchangeMethodID::UnbindOk => Self::UnbindOk(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueFrame {
+    Declare(queue::Declare),
+    DeclareOk(queue::DeclareOk),
+    Bind(queue::Bind),
+    BindOk(queue::BindOk),
+    Purge(queue::Purge),
+    PurgeOk(queue::PurgeOk),
+    Delete(queue::Delete),
+    DeleteOk(queue::DeleteOk),
+    Unbind(queue::Unbind),
+    UnbindOk(queue::UnbindOk),
+}
+
+impl QueueFrame {
+    fn decode(
+        method_id: QueueMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            QueueMethodID::Declare => Self::Declare(decode_frame(buffer)?),
+            QueueMethodID::DeclareOk => Self::DeclareOk(decode_frame(buffer)?),
+            QueueMethodID::Bind => Self::Bind(decode_frame(buffer)?),
+            QueueMethodID::BindOk => Self::BindOk(decode_frame(buffer)?),
+            QueueMethodID::Purge => Self::Purge(decode_frame(buffer)?),
+            QueueMethodID::PurgeOk => Self::PurgeOk(decode_frame(buffer)?),
+            QueueMethodID::Delete => Self::Delete(decode_frame(buffer)?),
+            QueueMethodID::DeleteOk => Self::DeleteOk(decode_frame(buffer)?),
+            QueueMethodID::Unbind => Self::Unbind(decode_frame(buffer)?),
+            QueueMethodID::UnbindOk => Self::UnbindOk(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BasicFrame {
+    QualityOfService(basic::QualityOfService),
+    QualityOfServiceOk(basic::QualityOfServiceOk),
+    Consume(basic::Consume),
+    ConsumeOk(basic::ConsumeOk),
+    Cancel(basic::Cancel),
+    CancelOk(basic::CancelOk),
+    Publish(basic::Publish),
+    Return(basic::Return),
+    Deliver(basic::Deliver),
+    Get(basic::Get),
+    GetOk(basic::GetOk),
+    GetEmpty(basic::GetEmpty),
+    Ack(basic::Ack),
+    Reject(basic::Reject),
+    Recover(basic::Recover),
+    RecoverOk(basic::RecoverOk),
+    Nack(basic::Nack),
+}
+
+impl BasicFrame {
+    fn decode(
+        method_id: BasicMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            BasicMethodID::QualityOfService => Self::QualityOfService(decode_frame(buffer)?),
+            BasicMethodID::QualityOfServiceOk => Self::QualityOfServiceOk(decode_frame(buffer)?),
+            BasicMethodID::Consume => Self::Consume(decode_frame(buffer)?),
+            BasicMethodID::ConsumeOk => Self::ConsumeOk(decode_frame(buffer)?),
+            BasicMethodID::Cancel => Self::Cancel(decode_frame(buffer)?),
+            BasicMethodID::CancelOk => Self::CancelOk(decode_frame(buffer)?),
+            BasicMethodID::Publish => Self::Publish(decode_frame(buffer)?),
+            BasicMethodID::Return => Self::Return(decode_frame(buffer)?),
+            BasicMethodID::Deliver => Self::Deliver(decode_frame(buffer)?),
+            BasicMethodID::Get => Self::Get(decode_frame(buffer)?),
+            BasicMethodID::GetOk => Self::GetOk(decode_frame(buffer)?),
+            BasicMethodID::GetEmpty => Self::GetEmpty(decode_frame(buffer)?),
+            BasicMethodID::Ack => Self::Ack(decode_frame(buffer)?),
+            BasicMethodID::Reject => Self::Reject(decode_frame(buffer)?),
+            BasicMethodID::Recover => Self::Recover(decode_frame(buffer)?),
+            BasicMethodID::RecoverOk => Self::RecoverOk(decode_frame(buffer)?),
+            BasicMethodID::Nack => Self::Nack(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmFrame {
+    Select(confirm::Select),
+    SelectOk(confirm::SelectOk),
+}
+
+impl ConfirmFrame {
+    fn decode(
+        method_id: ConfirmMethodID,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            ConfirmMethodID::Select => Self::Select(decode_frame(buffer)?),
+            ConfirmMethodID::SelectOk => Self::SelectOk(decode_frame(buffer)?),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionFrame {
+    Select(transaction::Select),
+    SelectOk(transaction::SelectOk),
+    Commit(transaction::Commit),
+    CommitOk(transaction::CommitOk),
+    Rollback(transaction::Rollback),
+    RollbackOk(transaction::RollbackOk),
+}
+
+impl TransactionFrame {
+    fn decode(
+        method_id: TransactionMethodId,
+        buffer: &[u8],
+    ) -> Result<Self, bincode::error::DecodeError> {
+        Ok(match method_id {
+            TransactionMethodId::Select => Self::Select(decode_frame(buffer)?),
+            TransactionMethodId::SelectOk => Self::SelectOk(decode_frame(buffer)?),
+            TransactionMethodId::Commit => Self::Commit(decode_frame(buffer)?),
+            TransactionMethodId::CommitOk => Self::CommitOk(decode_frame(buffer)?),
+            TransactionMethodId::Rollback => Self::Rollback(decode_frame(buffer)?),
+            TransactionMethodId::RollbackOk => Self::RollbackOk(decode_frame(buffer)?),
+        })
+    }
+}
+
+// Unwraps the reply to a synchronous method from a decoded frame, handing back
+// any other frame as the error
+macro_rules! impl_reply {
+    ($($class:ident($frame:ident::$method:ident) => $reply:ty),* $(,)?) => {
+        $(
+            impl TryFrom<AmqpFrame> for $reply {
+                type Error = AmqpFrame;
+
+                fn try_from(frame: AmqpFrame) -> Result<Self, Self::Error> {
+                    match frame {
+                        AmqpFrame::$class($frame::$method(reply)) => Ok(reply),
+                        frame => Err(frame),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_reply! {
+    Channel(ChannelFrame::OpenOk) => channel::OpenOk,
+    Channel(ChannelFrame::FlowOk) => channel::FlowOk,
+    Channel(ChannelFrame::CloseOk) => channel::CloseOk,
+    Exchange(ExchangeFrame::DeclareOk) => exchange::DeclareOk,
+    Exchange(ExchangeFrame::DeleteOk) => exchange::DeleteOk,
+    Exchange(ExchangeFrame::BindOk) => exchange::BindOk,
+    Exchange(ExchangeFrame::UnbindOk) => exchange::UnbindOk,
+    Queue(QueueFrame::DeclareOk) => queue::DeclareOk,
+    Queue(QueueFrame::BindOk) => queue::BindOk,
+    Queue(QueueFrame::PurgeOk) => queue::PurgeOk,
+    Queue(QueueFrame::DeleteOk) => queue::DeleteOk,
+    Queue(QueueFrame::UnbindOk) => queue::UnbindOk,
+    Basic(BasicFrame::QualityOfServiceOk) => basic::QualityOfServiceOk,
+    Basic(BasicFrame::ConsumeOk) => basic::ConsumeOk,
+    Basic(BasicFrame::CancelOk) => basic::CancelOk,
+    Basic(BasicFrame::RecoverOk) => basic::RecoverOk,
+    Confirm(ConfirmFrame::SelectOk) => confirm::SelectOk,
+    Transaction(TransactionFrame::SelectOk) => transaction::SelectOk,
+    Transaction(TransactionFrame::CommitOk) => transaction::CommitOk,
+    Transaction(TransactionFrame::RollbackOk) => transaction::RollbackOk,
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn test_decode_any_frame() {
+        let bytes = encode_frame(basic::CancelOk::new(2, "consumer")).unwrap();
+        let AmqpFrame::Basic(BasicFrame::CancelOk(cancel_ok)) = AmqpFrame::decode(&bytes).unwrap()
+        else {
+            panic!("Expected basic.cancel-ok");
+        };
+        assert_eq!(cancel_ok.consumer_tag.as_str(), "consumer");
+
+        let bytes = encode_frame(channel::CloseOk::new(2)).unwrap();
+        assert!(matches!(
+            AmqpFrame::decode(&bytes).unwrap(),
+            AmqpFrame::Channel(ChannelFrame::CloseOk(_))
+        ));
+
+        let bytes = encode_frame(content::Content::new(2, 5, Properties::default())).unwrap();
+        let AmqpFrame::Content(content_header) = AmqpFrame::decode(&bytes).unwrap() else {
+            panic!("Expected a content header");
+        };
+        assert_eq!(content_header.size, 5);
+
+        assert!(matches!(
+            AmqpFrame::decode(&HEARTBEAT).unwrap(),
+            AmqpFrame::Heartbeat
+        ));
+    }
+
+    #[test]
+    fn test_encode_into_buffer() {
+        let body = body::Body::new(1, RawBytes(bytes::Bytes::from_static(b"payload")));
+        let mut buffer = bytes::BytesMut::new();
+        encode_frame_into(&body, &mut buffer).unwrap();
+        encode_frame_into(&body, &mut buffer).unwrap();
+
+        let expected = encode_frame(&body).unwrap();
+        let frame = buffer.split_to(expected.len()).freeze();
+        assert_eq!(frame, expected);
+        assert_eq!(buffer, expected[..]);
+        assert_eq!(body::Body::payload(&frame).unwrap(), &b"payload"[..]);
+
+        // A frame that fails to encode leaves nothing behind
+        let publish = basic::Publish::new(1, "events", &"k".repeat(256), false, false);
+        assert!(encode_frame_into(&publish, &mut buffer).is_err());
+        assert_eq!(buffer, expected[..]);
+    }
+
+    #[test]
+    fn test_bit_fields() {
+        // queue.declare with durable and auto_delete set, laid out as in the spec:
+        // header, class and method, reserved, queue, packed bits, arguments
+        let declare = queue::Declare::new(1, "q", false, true, false, true, false);
+        let expected: Vec<u8> = [
+            &[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0D][..],
+            &[0x00, 0x32, 0x00, 0x0A, 0x00, 0x00],
+            &[0x01, b'q', 0b0000_1010],
+            &[0x00, 0x00, 0x00, 0x00, FRAME_END],
+        ]
+        .concat();
+        assert_eq!(encode_frame(declare).unwrap(), expected);
+
+        // basic.deliver: header, class and method, consumer tag, delivery tag,
+        // redelivered, exchange, routing key
+        let deliver: Vec<u8> = [
+            &[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x12][..],
+            &[0x00, 0x3C, 0x00, 0x3C, 0x01, b'c'],
+            &5_u64.to_be_bytes(),
+            &[0x01, 0x00, 0x01, b'q', FRAME_END],
+        ]
+        .concat();
+        let AmqpFrame::Basic(BasicFrame::Deliver(deliver)) = AmqpFrame::decode(&deliver).unwrap()
+        else {
+            panic!("Expected basic.deliver");
+        };
+        assert!(deliver.redelivered());
+        assert_eq!(deliver.delivery_tag, 5);
+    }
+
+    proptest! {
+        // Whatever follows a valid looking method header, decoding fails cleanly
+        // instead of panicking
+        #[test]
+        fn test_decode_arbitrary_frame(
+            frame_type in prop::sample::select(vec![1_u8, 2, 3, 8, 0]),
+            class_id in prop::sample::select(vec![10_u16, 20, 40, 50, 60, 85, 90]),
+            method_id in 0_u16..130,
+            tail in prop::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let frame = [
+                &[frame_type, 0, 1][..],
+                &(tail.len() as u32 + 4).to_be_bytes(),
+                &class_id.to_be_bytes(),
+                &method_id.to_be_bytes(),
+                &tail,
+            ]
+            .concat();
+            let _ = AmqpFrame::decode(&frame);
+            let _ = body::Body::payload(&Bytes::from(frame));
+        }
+
+        #[test]
+        fn test_body_round_trip(channel_id: u16, content in prop::collection::vec(any::<u8>(), 0..1024)) {
+            let frame = encode_frame(body::Body::new(channel_id, RawBytes(content.clone().into())))
+                .unwrap();
+            prop_assert_eq!(body::Body::payload(&frame.clone().into()).unwrap(), content.clone());
+            let AmqpFrame::Body(decoded) = AmqpFrame::decode(&frame).unwrap() else {
+                panic!("Expected a body frame");
+            };
+            prop_assert_eq!(decoded.header.channel_id, channel_id);
+            prop_assert_eq!(&decoded.content[..], &content[..]);
+        }
+    }
+}
@@ -1,18 +1,18 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 struct QueueFrameInfo {
     header: Header,
     class_id: ClassID,
     method_id: QueueMethodID,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Declare {
     frame_info: QueueFrameInfo,
     reserved_1: u16,
     queue: ShortString,
-    bits: Bits,
+    bits: Bits<5>,
     arguments: Table,
 }
 
@@ -46,32 +46,54 @@ impl Declare {
             arguments: Table::default(),
         }
     }
+
+    pub fn passive(&self) -> bool {
+        self.bits[0]
+    }
+    pub fn durable(&self) -> bool {
+        self.bits[1]
+    }
+    pub fn exclusive(&self) -> bool {
+        self.bits[2]
+    }
+    pub fn auto_delete(&self) -> bool {
+        self.bits[3]
+    }
+    pub fn no_wait(&self) -> bool {
+        self.bits[4]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct DeclareOk {
     frame_info: QueueFrameInfo,
     pub queue_name: ShortString,
     pub message_count: u32,
-    _consumer_count: u32,
+    pub consumer_count: u32,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Bind {
     frame_info: QueueFrameInfo,
     reserved_1: u16,
     queue: ShortString,
     exchange: ShortString,
     routing_key: ShortString,
-    no_wait: Bits,
+    no_wait: Bits<1>,
     arguments: Table,
 }
 
 impl Bind {
-    pub fn new(queue: &str, exchange: &str, routing_key: &str, no_wait: bool) -> Self {
+    pub fn new(
+        channel_id: u16,
+        queue: &str,
+        exchange: &str,
+        routing_key: &str,
+        no_wait: bool,
+    ) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Queue;
@@ -91,14 +113,18 @@ impl Bind {
             arguments: Table::default(),
         }
     }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct BindOk {
     frame_info: QueueFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Unbind {
     frame_info: QueueFrameInfo,
     reserved_1: u16,
@@ -109,10 +135,10 @@ pub struct Unbind {
 }
 
 impl Unbind {
-    pub fn new(queue: &str, exchange: &str, routing_key: &str) -> Self {
+    pub fn new(channel_id: u16, queue: &str, exchange: &str, routing_key: &str) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Queue;
@@ -133,23 +159,23 @@ impl Unbind {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct UnbindOk {
     frame_info: QueueFrameInfo,
 }
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Purge {
     frame_info: QueueFrameInfo,
     reserved_1: u16,
     queue: ShortString,
-    no_wait: Bits,
+    no_wait: Bits<1>,
 }
 
 impl Purge {
-    pub fn new(queue: &str, no_wait: bool) -> Self {
+    pub fn new(channel_id: u16, queue: &str, no_wait: bool) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Queue;
@@ -166,26 +192,36 @@ impl Purge {
             no_wait: (no_wait,).into(),
         }
     }
+
+    pub fn no_wait(&self) -> bool {
+        self.no_wait[0]
+    }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct PurgeOk {
     frame_info: QueueFrameInfo,
     message_count: u32,
 }
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Delete {
     frame_info: QueueFrameInfo,
     reserved_1: u16,
     queue: ShortString,
-    ifunused_ifempty_nowait: Bits,
+    ifunused_ifempty_nowait: Bits<3>,
 }
 
 impl Delete {
-    pub fn new(queue: &str, if_unused: bool, if_empty: bool, no_wait: bool) -> Self {
+    pub fn new(
+        channel_id: u16,
+        queue: &str,
+        if_unused: bool,
+        if_empty: bool,
+        no_wait: bool,
+    ) -> Self {
         let header = Header {
             frame_type: FrameType::Method,
-            channel_id: 1,
+            channel_id,
             size: 0,
         };
         let class_id = ClassID::Queue;
@@ -202,9 +238,62 @@ impl Delete {
             ifunused_ifempty_nowait: (if_unused, if_empty, no_wait).into(),
         }
     }
+
+    pub fn if_unused(&self) -> bool {
+        self.ifunused_ifempty_nowait[0]
+    }
+    pub fn if_empty(&self) -> bool {
+        self.ifunused_ifempty_nowait[1]
+    }
+    pub fn no_wait(&self) -> bool {
+        self.ifunused_ifempty_nowait[2]
+    }
 }
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct DeleteOk {
     frame_info: QueueFrameInfo,
     message_count: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(
+            channel_id: u16,
+            queue in string(),
+            exchange in string(),
+            routing_key in string(),
+            flags: [bool; 5],
+            counts: (u32, u32),
+        ) {
+            let info = |method_id| QueueFrameInfo {
+                header: method_header(channel_id),
+                class_id: ClassID::Queue,
+                method_id,
+            };
+            let [a, b, c, d, e] = flags;
+            let (message_count, consumer_count) = counts;
+            assert_round_trip(Declare::new(channel_id, &queue, a, b, c, d, e))?;
+            assert_round_trip(DeclareOk {
+                frame_info: info(QueueMethodID::DeclareOk),
+                queue_name: queue.as_str().into(),
+                message_count,
+                consumer_count,
+            })?;
+            assert_round_trip(Bind::new(channel_id, &queue, &exchange, &routing_key, a))?;
+            assert_round_trip(BindOk { frame_info: info(QueueMethodID::BindOk) })?;
+            assert_round_trip(Unbind::new(channel_id, &queue, &exchange, &routing_key))?;
+            assert_round_trip(UnbindOk { frame_info: info(QueueMethodID::UnbindOk) })?;
+            assert_round_trip(Purge::new(channel_id, &queue, a))?;
+            assert_round_trip(PurgeOk { frame_info: info(QueueMethodID::PurgeOk), message_count })?;
+            assert_round_trip(Delete::new(channel_id, &queue, a, b, c))?;
+            assert_round_trip(DeleteOk { frame_info: info(QueueMethodID::DeleteOk), message_count })?;
+        }
+    }
+}
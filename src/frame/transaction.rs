@@ -1,13 +1,13 @@
 use crate::encde::*;
 
-#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct TransactionFrameInfo {
     header: Header,
     class_id: ClassID,
     method_id: TransactionMethodId,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Select {
     frame_info: TransactionFrameInfo,
 }
@@ -30,12 +30,12 @@ impl Select {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct SelectOk {
     frame_info: TransactionFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Commit {
     frame_info: TransactionFrameInfo,
 }
@@ -58,12 +58,12 @@ impl Commit {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct CommitOk {
     frame_info: TransactionFrameInfo,
 }
 
-#[derive(Debug, Clone, bincode::Encode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct Rollback {
     frame_info: TransactionFrameInfo,
 }
@@ -86,7 +86,32 @@ impl Rollback {
     }
 }
 
-#[derive(Debug, Clone, bincode::Decode)]
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
 pub struct RollbackOk {
     frame_info: TransactionFrameInfo,
 }
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::encde::strategies::*;
+
+    proptest! {
+        #[test]
+        fn test_round_trip(channel_id: u16) {
+            let info = |method_id| TransactionFrameInfo {
+                header: method_header(channel_id),
+                class_id: ClassID::Transaction,
+                method_id,
+            };
+            assert_round_trip(Select::new(channel_id))?;
+            assert_round_trip(SelectOk { frame_info: info(TransactionMethodId::SelectOk) })?;
+            assert_round_trip(Commit::new(channel_id))?;
+            assert_round_trip(CommitOk { frame_info: info(TransactionMethodId::CommitOk) })?;
+            assert_round_trip(Rollback::new(channel_id))?;
+            assert_round_trip(RollbackOk { frame_info: info(TransactionMethodId::RollbackOk) })?;
+        }
+    }
+}
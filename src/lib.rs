@@ -1,16 +1,32 @@
 #![allow(dead_code)]
 mod client_connection;
 mod connection_parameters;
+mod dispatcher;
 mod encde;
 mod frame;
 mod tcp;
 
 pub mod client;
+#[cfg(feature = "serde")]
+pub mod codec;
+pub mod compression;
+pub mod consumer;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzz;
+pub mod rpc;
 pub mod types;
 
 pub use client::Client;
-pub use connection_parameters::{ConnectionParameters, ConnectionParametersBuilder};
+pub use compression::Compression;
+pub use connection_parameters::{
+    BlockedBehaviour, ConnectionParameters, ConnectionParametersBuilder,
+};
+pub use consumer::{CancelReason, Consumer, ConsumerEvent};
+pub use rpc::{Reply, ReplyQueue, RpcClient, RpcHandler, RpcServer};
 
+pub use encde::properties::{DeliveryMode, PropertiesBuilder};
 pub use encde::ExchangeType;
 pub use encde::Properties;
+pub use encde::{Field, LongString, ShortString, Table, TableBuilder};
 pub use types::*;
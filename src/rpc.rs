@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::*;
+
+// RabbitMQ pseudo queue that routes replies straight back to the consuming channel
+pub const DIRECT_REPLY_TO: &str = "amq.rabbitmq.reply-to";
+
+pub type RpcHandler = &'static (dyn Fn(Message) -> Reply + Send + Sync);
+
+pub struct Reply {
+    pub body: Vec<u8>,
+    // Defaults to Properties::default() when not set. correlation_id is always
+    // copied from the request.
+    pub properties: Option<Properties>,
+}
+
+impl Reply {
+    pub fn new(body: Vec<u8>) -> Self {
+        Self {
+            body,
+            properties: None,
+        }
+    }
+
+    pub fn with_properties(body: Vec<u8>, properties: Properties) -> Self {
+        Self {
+            body,
+            properties: Some(properties),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Calls {
+    replies: HashMap<String, oneshot::Sender<Message>>,
+    // Set once the reply consumer is cancelled, no call can complete after that
+    cancelled: Option<CancelReason>,
+}
+
+type Pending = Arc<Mutex<Calls>>;
+
+pub enum ReplyQueue {
+    // Direct reply-to, no queue is declared
+    DirectReplyTo,
+    // A server named exclusive queue, declared once for the lifetime of the client
+    Exclusive,
+}
+
+pub struct RpcClient {
+    client: tokio::sync::Mutex<Client>,
+    reply_to: String,
+    pending: Pending,
+}
+
+impl RpcClient {
+    pub async fn new(mut client: Client, reply_queue: ReplyQueue) -> Result<Self> {
+        let reply_to = match reply_queue {
+            ReplyQueue::DirectReplyTo => DIRECT_REPLY_TO.into(),
+            ReplyQueue::Exclusive => {
+                let queue_definition = QueueDefinition::builder()
+                    .exclusive(true)
+                    .auto_delete(true)
+                    .build();
+                client.create_queue(queue_definition).await?.name
+            }
+        };
+        // Direct reply-to requires no_ack, and the replies are matched rather than handled
+        let options = ConsumeOptions::builder()
+            .consumer_tag(ConsumerTag::Generated)
+            .no_ack(true)
+            .build();
+        let consumer = client.start_consumer(&reply_to, &options).await?;
+        Ok(Self::with_consumer(client, reply_to, consumer))
+    }
+
+    fn with_consumer(client: Client, reply_to: String, consumer: Consumer) -> Self {
+        let pending = Pending::default();
+        let task_pending = pending.clone();
+        tokio::task::spawn(async move { reply_task(consumer, task_pending).await });
+        Self {
+            client: tokio::sync::Mutex::new(client),
+            reply_to,
+            pending,
+        }
+    }
+
+    // Publishes the request and waits for the matching reply. Dropping the returned
+    // future cancels the call, a reply arriving afterwards is discarded. Once the
+    // reply consumer is cancelled every call fails without publishing.
+    pub async fn call(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        body: &[u8],
+        properties: Properties,
+    ) -> Result<Message> {
+        let correlation_id = generate_correlation_id();
+        let properties = properties
+            .into_builder()
+            .correlation_id(correlation_id.clone())
+            .reply_to(self.reply_to.clone())
+            .build();
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some(reason) = &pending.cancelled {
+                return Err(format!("Reply consumer cancelled: {reason:?}").into());
+            }
+            pending.replies.insert(correlation_id.clone(), sender);
+        }
+        let _guard = PendingGuard {
+            pending: self.pending.clone(),
+            correlation_id,
+        };
+
+        self.client
+            .lock()
+            .await
+            .publish(exchange, routing_key, body, properties)
+            .await?;
+        let reply = receiver.await.map_err(|_| "Reply consumer cancelled")?;
+        Ok(reply)
+    }
+
+    pub async fn call_with_timeout(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        body: &[u8],
+        properties: Properties,
+        timeout: Duration,
+    ) -> Result<Message> {
+        match tokio::time::timeout(timeout, self.call(exchange, routing_key, body, properties))
+            .await
+        {
+            Ok(reply) => reply,
+            Err(_) => Err(Error::Timeout.into()),
+        }
+    }
+}
+
+// Consumes requests from a queue and publishes the handler's reply to each
+// request's reply_to queue through the default exchange
+pub struct RpcServer {
+    // Owns the connection the requests are consumed on
+    _client: Client,
+    consumer: Consumer,
+}
+
+impl RpcServer {
+    pub async fn new(mut client: Client, queue: &str, mut options: ConsumeOptions) -> Result<Self> {
+        // Requests are acked once their reply has been published
+        options.no_ack = false;
+        let consumer = client.start_consumer(queue, &options).await?;
+        Ok(Self {
+            _client: client,
+            consumer,
+        })
+    }
+
+    // Failures to reply are reported as ConnectionEvent::Error
+    pub async fn run(mut self, handler: RpcHandler) -> CancelReason {
+        let responder = self.consumer.responder();
+        loop {
+            match self.consumer.recv().await {
+                ConsumerEvent::Delivery(message) => {
+                    responder.respond_in_task(*message, |message| Some(handler(message)))
+                }
+                ConsumerEvent::Cancelled(reason) => return reason,
+            }
+        }
+    }
+}
+
+pub(crate) fn reply_properties(request: &Properties, reply: Option<Properties>) -> Properties {
+    let properties = reply.unwrap_or_default();
+    match request.correlation_id() {
+        Some(correlation_id) => properties
+            .into_builder()
+            .correlation_id(correlation_id.into())
+            .build(),
+        None => properties,
+    }
+}
+
+// Removes the pending call once it completes, times out or is dropped
+struct PendingGuard {
+    pending: Pending,
+    correlation_id: String,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.pending
+            .lock()
+            .unwrap()
+            .replies
+            .remove(&self.correlation_id);
+    }
+}
+
+fn generate_correlation_id() -> String {
+    static ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+    let count = ID_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    format!("byteflow-{}-{count}", std::process::id())
+}
+
+async fn reply_task(mut consumer: Consumer, pending: Pending) {
+    loop {
+        match consumer.recv().await {
+            ConsumerEvent::Delivery(message) => {
+                // A reply without a correlation id cannot be matched, and one for an
+                // unknown call arrived after the call timed out or was dropped
+                let Some(correlation_id) = message.properties.correlation_id() else {
+                    continue;
+                };
+                let sender = pending.lock().unwrap().replies.remove(correlation_id);
+                if let Some(sender) = sender {
+                    let _ = sender.send(*message);
+                }
+            }
+            ConsumerEvent::Cancelled(reason) => {
+                // Dropping the senders fails every outstanding call
+                let mut pending = pending.lock().unwrap();
+                pending.replies.clear();
+                pending.cancelled = Some(reason);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::client::tests::client;
+    use crate::consumer::Responder;
+    use crate::dispatcher::{FlowState, SharedListeners};
+    use crate::encde::*;
+    use crate::frame::*;
+
+    struct Peer {
+        rpc_client: Arc<RpcClient>,
+        written: UnboundedReceiver<Bytes>,
+        replies: UnboundedSender<ConsumerEvent>,
+    }
+
+    fn rpc_client() -> Peer {
+        let (client, written, _) = client();
+        let (replies, receiver) = mpsc::unbounded_channel();
+        let (writer, _) = mpsc::unbounded_channel();
+        let (_, flow) = watch::channel(FlowState::default());
+        let responder = Responder::new(
+            1,
+            true,
+            131072,
+            writer,
+            SharedListeners::default(),
+            flow,
+            BlockedBehaviour::Fail,
+        );
+        let consumer = Consumer::new("ctag-rpc".into(), responder, 1024, receiver);
+        let rpc_client = RpcClient::with_consumer(client, DIRECT_REPLY_TO.into(), consumer);
+        Peer {
+            rpc_client: Arc::new(rpc_client),
+            written,
+            replies,
+        }
+    }
+
+    // Starts a call on a task of its own, returning it with the request it published
+    async fn call(
+        peer: &mut Peer,
+        body: &'static [u8],
+        timeout: Duration,
+    ) -> (
+        tokio::task::JoinHandle<std::result::Result<Bytes, String>>,
+        String,
+    ) {
+        let rpc_client = peer.rpc_client.clone();
+        let call = tokio::task::spawn(async move {
+            rpc_client
+                .call_with_timeout("", "requests", body, Properties::default(), timeout)
+                .await
+                .map(|reply| reply.bytes)
+                .map_err(|e| e.to_string())
+        });
+        // basic.publish, the content header and a single body frame
+        let published = peer.written.recv().await.unwrap();
+        let method_size =
+            HEADER_SIZE + published[3..7].iter().fold(0, |n, b| n << 8 | *b as usize) + 1;
+        let AmqpFrame::Content(content_header) =
+            AmqpFrame::decode(&published[method_size..]).unwrap()
+        else {
+            panic!("Expected a content header");
+        };
+        let properties = content_header.properties;
+        assert_eq!(properties.reply_to(), Some(DIRECT_REPLY_TO));
+        assert!(published.ends_with(&[body, &[FRAME_END]].concat()));
+        (call, properties.correlation_id().unwrap().into())
+    }
+
+    fn reply(correlation_id: &str, body: &'static [u8]) -> ConsumerEvent {
+        let properties = Properties::builder()
+            .correlation_id(correlation_id.into())
+            .build();
+        let additional_info = AdditionalInfo {
+            channel_id: 1,
+            queue: DIRECT_REPLY_TO.into(),
+            consumer_tag: Some("ctag-rpc".into()),
+            delivery_tag: 1,
+            redelivered: false,
+            exchange: "".into(),
+            routing_key: DIRECT_REPLY_TO.into(),
+            message_count: None,
+        };
+        let message = Message::new(Bytes::from_static(body), properties, additional_info);
+        ConsumerEvent::Delivery(Box::new(message))
+    }
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[tokio::test]
+    async fn test_replies_are_matched_by_correlation_id() {
+        let mut peer = rpc_client();
+        let (first, first_id) = call(&mut peer, b"first", TIMEOUT).await;
+        let (second, second_id) = call(&mut peer, b"second", TIMEOUT).await;
+        assert_ne!(first_id, second_id);
+
+        peer.replies.send(reply("unknown", b"stray")).unwrap();
+        peer.replies
+            .send(reply(&second_id, b"second reply"))
+            .unwrap();
+        peer.replies.send(reply(&first_id, b"first reply")).unwrap();
+        assert_eq!(first.await.unwrap().unwrap(), &b"first reply"[..]);
+        assert_eq!(second.await.unwrap().unwrap(), &b"second reply"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_late_reply_is_discarded() {
+        let mut peer = rpc_client();
+        let (late, late_id) = call(&mut peer, b"late", Duration::from_millis(10)).await;
+        assert_eq!(late.await.unwrap(), Err(Error::Timeout.to_string()));
+        assert!(peer.rpc_client.pending.lock().unwrap().replies.is_empty());
+
+        let (next, next_id) = call(&mut peer, b"next", TIMEOUT).await;
+        peer.replies.send(reply(&late_id, b"late reply")).unwrap();
+        peer.replies.send(reply(&next_id, b"next reply")).unwrap();
+        assert_eq!(next.await.unwrap().unwrap(), &b"next reply"[..]);
+    }
+
+    #[tokio::test]
+    async fn test_calls_fail_after_the_reply_consumer_is_cancelled() {
+        let mut peer = rpc_client();
+        let (outstanding, _) = call(&mut peer, b"outstanding", TIMEOUT).await;
+        peer.replies
+            .send(ConsumerEvent::Cancelled(CancelReason::Server))
+            .unwrap();
+        assert!(outstanding.await.unwrap().is_err());
+
+        // Fails at once, without publishing the request
+        let result = peer
+            .rpc_client
+            .call("", "requests", b"after", Properties::default())
+            .await;
+        assert!(result.is_err());
+        assert!(peer.written.try_recv().is_err());
+    }
+}
@@ -1,33 +1,63 @@
+use bytes::{Bytes, BytesMut};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf},
     net::TcpStream,
     sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
 };
 
+use crate::encde::HEADER_SIZE;
+
+// Matches RabbitMQ's default frame_max, so a full frame usually fits without growing
+const READ_BUFFER_SIZE: usize = 128 * 1024;
+
 struct AdapterReader {
     tcp_reader: ReadHalf<TcpStream>,
-    sender: UnboundedSender<Vec<u8>>,
+    sender: UnboundedSender<Bytes>,
+    buffer: BytesMut,
 }
 
 impl AdapterReader {
     pub async fn start(&mut self) {
         loop {
-            let mut buffer = [0_u8; 1024];
-            match self.tcp_reader.read(&mut buffer).await {
-                Ok(_) => {
-                    if let Err(e) = self.sender.send(buffer.to_vec()) {
-                        println!("Error sending to Client {:?}", e);
-                    }
-                    continue;
+            // Either end closing drops the sender, the dispatcher then ends and cancels
+            // every consumer with CancelReason::ConnectionClosed
+            let Ok(frame) = self.read_frame().await else {
+                break;
+            };
+            if self.sender.send(frame).is_err() {
+                break;
+            }
+        }
+    }
+
+    // Splits complete frames off the receive buffer, reading the header to find the
+    // payload size. Frames share the buffer's allocation rather than being copied out.
+    async fn read_frame(&mut self) -> std::io::Result<Bytes> {
+        loop {
+            if self.buffer.len() >= HEADER_SIZE {
+                let size = u32::from_be_bytes([
+                    self.buffer[3],
+                    self.buffer[4],
+                    self.buffer[5],
+                    self.buffer[6],
+                ]) as usize;
+                let frame_size = HEADER_SIZE + size + 1; // Payload and frame end
+                if self.buffer.len() >= frame_size {
+                    return Ok(self.buffer.split_to(frame_size).freeze());
                 }
-                Err(_) => todo!(),
+                self.buffer.reserve(frame_size - self.buffer.len());
+            } else {
+                self.buffer.reserve(READ_BUFFER_SIZE);
+            }
+            if self.tcp_reader.read_buf(&mut self.buffer).await? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
             }
         }
     }
 }
 struct AdapterWriter {
     tcp_writer: WriteHalf<TcpStream>,
-    receiver: UnboundedReceiver<Vec<u8>>,
+    receiver: UnboundedReceiver<Bytes>,
 }
 
 impl AdapterWriter {
@@ -38,12 +68,12 @@ impl AdapterWriter {
     }
 }
 pub struct TcpAdapter {
-    tcp_sender: UnboundedSender<Vec<u8>>,
-    tcp_receiver: UnboundedReceiver<Vec<u8>>,
+    tcp_sender: UnboundedSender<Bytes>,
+    tcp_receiver: UnboundedReceiver<Bytes>,
 }
 
 impl TcpAdapter {
-    pub fn clone_sender(&self) -> UnboundedSender<Vec<u8>> {
+    pub fn clone_sender(&self) -> UnboundedSender<Bytes> {
         self.tcp_sender.clone()
     }
     pub async fn new(address: &str) -> Self {
@@ -52,7 +82,7 @@ impl TcpAdapter {
             .expect("Failed to create TCP connection. Is RabbitMQ running?");
         let (tcp_reader, tcp_writer) = tokio::io::split(stream);
 
-        let (tcp_sender, receiver): (UnboundedSender<Vec<u8>>, UnboundedReceiver<Vec<u8>>) =
+        let (tcp_sender, receiver): (UnboundedSender<Bytes>, UnboundedReceiver<Bytes>) =
             mpsc::unbounded_channel();
 
         let mut adapter_writer = AdapterWriter {
@@ -60,9 +90,13 @@ impl TcpAdapter {
             receiver,
         };
 
-        let (sender, tcp_receiver): (UnboundedSender<Vec<u8>>, UnboundedReceiver<Vec<u8>>) =
+        let (sender, tcp_receiver): (UnboundedSender<Bytes>, UnboundedReceiver<Bytes>) =
             mpsc::unbounded_channel();
-        let mut adapter_reader = AdapterReader { tcp_reader, sender };
+        let mut adapter_reader = AdapterReader {
+            tcp_reader,
+            sender,
+            buffer: BytesMut::with_capacity(READ_BUFFER_SIZE),
+        };
 
         tokio::task::spawn(async move {
             adapter_writer.start().await;
@@ -77,11 +111,15 @@ impl TcpAdapter {
         }
     }
 
-    pub async fn send(&self, bytes: Vec<u8>) {
-        self.tcp_sender.send(bytes).unwrap();
+    pub async fn send(&self, bytes: impl Into<Bytes>) {
+        self.tcp_sender.send(bytes.into()).unwrap();
     }
 
-    pub async fn receive(&mut self) -> Option<Vec<u8>> {
+    pub async fn receive(&mut self) -> Option<Bytes> {
         self.tcp_receiver.recv().await
     }
+
+    pub fn into_parts(self) -> (UnboundedSender<Bytes>, UnboundedReceiver<Bytes>) {
+        (self.tcp_sender, self.tcp_receiver)
+    }
 }
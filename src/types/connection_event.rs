@@ -0,0 +1,11 @@
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    // connection.blocked, e.g. the broker hit a memory or disk alarm
+    Blocked { reason: String },
+    Unblocked,
+    // channel.flow, publishing is paused while `active` is false
+    Flow { active: bool },
+    // A failure with no caller to return it to, e.g. a frame the dispatcher could not
+    // handle or a reply a consumer could not publish
+    Error { error: String },
+}
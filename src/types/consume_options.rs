@@ -0,0 +1,111 @@
+use std::sync::atomic::AtomicU64;
+
+use crate::encde::Table;
+
+#[derive(Debug, Clone, Default)]
+pub enum ConsumerTag {
+    // Empty tag, the server picks one and returns it in ConsumeOk
+    #[default]
+    Server,
+    // Unique tag generated by the client
+    Generated,
+    Named(String),
+}
+
+impl ConsumerTag {
+    pub fn resolve(&self) -> String {
+        match self {
+            ConsumerTag::Server => "".into(),
+            ConsumerTag::Generated => generate_consumer_tag(),
+            ConsumerTag::Named(tag) => tag.clone(),
+        }
+    }
+}
+
+fn generate_consumer_tag() -> String {
+    // Consumer tags only need to be unique within a channel
+    static TAG_COUNTER: AtomicU64 = AtomicU64::new(1);
+    let count = TAG_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    format!("byteflow-{}-{count}", std::process::id())
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConsumeOptions {
+    // Sent as a per-consumer basic.qos before consuming. With None a limit set by the
+    // prefetch_count of an earlier consumer is cleared, one set with
+    // Client::set_prefetch is kept.
+    pub prefetch_count: Option<u16>,
+    pub consumer_tag: ConsumerTag,
+    pub no_local: bool,
+    pub no_ack: bool,
+    pub exclusive: bool,
+    pub no_wait: bool,
+    pub arguments: Table,
+}
+
+impl ConsumeOptions {
+    pub fn builder() -> ConsumeOptionsBuilder {
+        ConsumeOptionsBuilder {
+            prefetch_count: None,
+            consumer_tag: None,
+            no_local: None,
+            no_ack: None,
+            exclusive: None,
+            no_wait: None,
+            arguments: None,
+        }
+    }
+}
+
+pub struct ConsumeOptionsBuilder {
+    prefetch_count: Option<u16>,
+    consumer_tag: Option<ConsumerTag>,
+    no_local: Option<bool>,
+    no_ack: Option<bool>,
+    exclusive: Option<bool>,
+    no_wait: Option<bool>,
+    arguments: Option<Table>,
+}
+
+impl ConsumeOptionsBuilder {
+    pub fn prefetch_count(mut self, prefetch_count: u16) -> Self {
+        self.prefetch_count = Some(prefetch_count);
+        self
+    }
+    pub fn consumer_tag(mut self, consumer_tag: ConsumerTag) -> Self {
+        self.consumer_tag = Some(consumer_tag);
+        self
+    }
+    pub fn no_local(mut self, no_local: bool) -> Self {
+        self.no_local = Some(no_local);
+        self
+    }
+    pub fn no_ack(mut self, no_ack: bool) -> Self {
+        self.no_ack = Some(no_ack);
+        self
+    }
+    pub fn exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = Some(exclusive);
+        self
+    }
+    pub fn no_wait(mut self, no_wait: bool) -> Self {
+        self.no_wait = Some(no_wait);
+        self
+    }
+    pub fn arguments(mut self, arguments: Table) -> Self {
+        self.arguments = Some(arguments);
+        self
+    }
+
+    pub fn build(self) -> ConsumeOptions {
+        ConsumeOptions {
+            prefetch_count: self.prefetch_count,
+            consumer_tag: self.consumer_tag.unwrap_or_default(),
+            no_local: self.no_local.unwrap_or(false),
+            no_ack: self.no_ack.unwrap_or(false),
+            exclusive: self.exclusive.unwrap_or(false),
+            no_wait: self.no_wait.unwrap_or(false),
+            arguments: self.arguments.unwrap_or_default(),
+        }
+    }
+}
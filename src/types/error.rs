@@ -0,0 +1,36 @@
+pub const NOT_FOUND: u16 = 404;
+
+#[derive(Debug)]
+pub enum Error {
+    QueueNotFound(String),
+    ChannelClosed { reply_code: u16, reply_text: String },
+    ConnectionClosed { reply_code: u16, reply_text: String },
+    PublishBlocked(String),
+    Timeout,
+    ContentTypeMismatch { expected: String, actual: String },
+    Table(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::QueueNotFound(queue) => write!(f, "Queue `{queue}` not found"),
+            Error::ChannelClosed {
+                reply_code,
+                reply_text,
+            } => write!(f, "Channel closed by server: {reply_code} {reply_text}"),
+            Error::ConnectionClosed {
+                reply_code,
+                reply_text,
+            } => write!(f, "Connection closed by server: {reply_code} {reply_text}"),
+            Error::PublishBlocked(reason) => write!(f, "Publishing blocked: {reason}"),
+            Error::Timeout => write!(f, "Timed out waiting for a reply"),
+            Error::ContentTypeMismatch { expected, actual } => {
+                write!(f, "Expected content type `{expected}`, found `{actual}`")
+            }
+            Error::Table(message) => write!(f, "Field table conversion failed: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
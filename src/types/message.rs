@@ -1,5 +1,6 @@
+pub use bytes::Bytes;
+
 use crate::Properties;
-pub type Bytes = Vec<u8>;
 
 pub struct Message {
     pub bytes: Bytes,
@@ -18,12 +19,27 @@ impl Message {
 }
 
 pub struct AdditionalInfo {
+    pub channel_id: u16,
+    pub queue: String,
+    // None for messages fetched with basic.get
+    pub consumer_tag: Option<String>,
     pub delivery_tag: u64,
+    pub redelivered: bool,
+    pub exchange: String,
+    pub routing_key: String,
+    // Messages remaining on the queue, only known for basic.get
+    pub message_count: Option<u32>,
 }
 
-impl AdditionalInfo {
-    pub fn new(delivery_tag: u64) -> Self {
-        Self { delivery_tag }
-    }
+// A mandatory or immediate publish the broker could not route, sent back with
+// the original properties and body
+pub struct ReturnedMessage {
+    pub reply_code: u16,
+    pub reply_text: String,
+    pub exchange: String,
+    pub routing_key: String,
+    pub bytes: Bytes,
+    pub properties: Properties,
 }
+
 pub type Handler = &'static (dyn Fn(Message) -> Option<Vec<u8>> + Send + Sync);
@@ -1,7 +1,22 @@
+pub mod connection_event;
+pub use connection_event::*;
+
+pub mod consume_options;
+pub use consume_options::*;
+
+pub mod error;
+pub use error::*;
+
 pub mod message;
 pub use message::*;
 
+pub mod publish_options;
+pub use publish_options::*;
+
 pub mod queue_definition;
 pub use queue_definition::*;
 
+pub mod queue_info;
+pub use queue_info::*;
+
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -0,0 +1,50 @@
+use crate::compression::Compression;
+
+#[derive(Debug, Clone, Default)]
+pub struct PublishOptions {
+    // Return the message if it cannot be routed to any queue
+    pub mandatory: bool,
+    // Not supported by RabbitMQ, which closes the connection when it is set
+    pub immediate: bool,
+    // Compresses the body and sets `content_encoding` to match
+    pub compression: Option<Compression>,
+}
+
+impl PublishOptions {
+    pub fn builder() -> PublishOptionsBuilder {
+        PublishOptionsBuilder {
+            mandatory: None,
+            immediate: None,
+            compression: None,
+        }
+    }
+}
+
+pub struct PublishOptionsBuilder {
+    mandatory: Option<bool>,
+    immediate: Option<bool>,
+    compression: Option<Compression>,
+}
+
+impl PublishOptionsBuilder {
+    pub fn mandatory(mut self, mandatory: bool) -> Self {
+        self.mandatory = Some(mandatory);
+        self
+    }
+    pub fn immediate(mut self, immediate: bool) -> Self {
+        self.immediate = Some(immediate);
+        self
+    }
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    pub fn build(self) -> PublishOptions {
+        PublishOptions {
+            mandatory: self.mandatory.unwrap_or(false),
+            immediate: self.immediate.unwrap_or(false),
+            compression: self.compression,
+        }
+    }
+}
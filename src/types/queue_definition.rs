@@ -79,7 +79,7 @@ impl QueueDefinitionBuilder {
             passive: self.passive.unwrap_or(false),
             durable: self.durable.unwrap_or(false),
             auto_delete: self.auto_delete.unwrap_or(false),
-            exclusive: self.auto_delete.unwrap_or(false),
+            exclusive: self.exclusive.unwrap_or(false),
             no_wait: self.no_wait.unwrap_or(false),
         }
     }
@@ -0,0 +1,6 @@
+#[derive(Debug, Clone)]
+pub struct QueueInfo {
+    pub name: String,
+    pub message_count: u32,
+    pub consumer_count: u32,
+}